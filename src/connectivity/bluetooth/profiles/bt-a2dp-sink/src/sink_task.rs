@@ -7,23 +7,297 @@ use {
     bt_a2dp::{codec::MediaCodecConfig, inspect::DataStreamInspect, media_task::*},
     bt_a2dp_sink_metrics as metrics,
     bt_avdtp::{self as avdtp, MediaStream},
-    fuchsia_async,
+    fidl::endpoints::create_request_stream,
+    fidl_fuchsia_bluetooth,
+    fidl_fuchsia_bluetooth_avrcp::{self as avrcp, ControllerEvent, PlaybackStatus},
+    fidl_fuchsia_media::{
+        AudioRenderUsage, Metadata, Property, Usage, UsageReporterMarker, UsageState,
+        UsageWatcherMarker, UsageWatcherRequest, UsageWatcherRequestStream,
+        METADATA_LABEL_ALBUM, METADATA_LABEL_ARTIST, METADATA_LABEL_TITLE,
+    },
+    fidl_fuchsia_media_sessions2::{
+        PlayerInfoDelta, PlayerMarker, PlayerRegistration, PlayerRequest, PlayerRequestStream,
+        PlayerState, PlayerStatus, PublisherMarker,
+    },
+    fuchsia_async::{self, Time, Timer},
     fuchsia_bluetooth::types::PeerId,
     fuchsia_cobalt::CobaltSender,
+    fuchsia_component::client::connect_to_service,
     fuchsia_syslog::{self, fx_log_info, fx_vlog},
     fuchsia_trace as trace,
+    fuchsia_zircon as zx,
     futures::{
+        channel::mpsc,
         future::{AbortHandle, Abortable, Aborted},
         lock::Mutex,
-        select, FutureExt, StreamExt,
+        select, FutureExt, Stream, StreamExt,
     },
     std::sync::Arc,
     thiserror::Error,
 };
 
 use crate::player;
+#[cfg(test)]
 use crate::DEFAULT_SESSION_ID;
 
+/// How long `decode_media_stream` will wait for a packet before flagging an underrun. Chosen to
+/// comfortably exceed normal inter-packet spacing while still catching a stalled source quickly.
+const STALL_TIMEOUT: zx::Duration = zx::Duration::from_seconds(2);
+
+/// Which edge of a higher-priority audio usage interruption a `Interruption` represents.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InterruptionStage {
+    /// The usage has started being ducked or muted; playback should yield.
+    Begin,
+    /// The usage is no longer adjusted; playback may resume.
+    End,
+}
+
+/// One hanging-get update from the platform `UsageReporter`, describing an interruption to the
+/// `Media` render usage (e.g. a phone call or navigation prompt taking over output).
+#[derive(Clone, Debug, PartialEq)]
+struct Interruption {
+    usage: Usage,
+    stage: InterruptionStage,
+}
+
+/// Turns a `UsageWatcherRequestStream` into a `Stream<Item = Interruption>`, acknowledging each
+/// `OnStateChanged` request via its responder as it's consumed so the hanging get keeps flowing.
+fn usage_interruption_stream(
+    requests: UsageWatcherRequestStream,
+) -> impl Stream<Item = Interruption> {
+    requests.filter_map(|request| async move {
+        let UsageWatcherRequest::OnStateChanged { usage, state, responder } = match request {
+            Ok(request) => request,
+            Err(e) => {
+                fx_log_info!("Usage watcher request stream error: {:?}", e);
+                return None;
+            }
+        };
+        let _ = responder.send();
+        let stage = match state {
+            UsageState::Unadjusted(_) => InterruptionStage::End,
+            UsageState::Ducked(_) | UsageState::Muted(_) => InterruptionStage::Begin,
+        };
+        Some(Interruption { usage, stage })
+    })
+}
+
+/// Connects to `UsageReporter` and starts watching the `Media` render usage, returning a stream
+/// of `Interruption`s as the platform ducks, mutes, or un-adjusts that usage.
+fn watch_media_usage_interruptions() -> Result<impl Stream<Item = Interruption>, Error> {
+    let usage_reporter = connect_to_service::<UsageReporterMarker>()?;
+    let (watcher_client, watcher_requests) = create_request_stream::<UsageWatcherMarker>()?;
+    let mut usage = Usage::RenderUsage(AudioRenderUsage::Media);
+    usage_reporter.watch(&mut usage, watcher_client)?;
+    Ok(usage_interruption_stream(watcher_requests))
+}
+
+/// Maps an AVRCP playback status to the media session player state it should be reflected as.
+fn playback_status_to_player_state(status: PlaybackStatus) -> PlayerState {
+    match status {
+        PlaybackStatus::Stopped => PlayerState::Idle,
+        PlaybackStatus::Playing => PlayerState::Playing,
+        PlaybackStatus::Paused => PlayerState::Paused,
+        PlaybackStatus::FwdSeek | PlaybackStatus::RevSeek => PlayerState::Playing,
+        PlaybackStatus::Error => PlayerState::Idle,
+    }
+}
+
+/// AVRCP's sentinel `track_id` value indicating no track is currently selected.
+const NO_TRACK_SELECTED: u64 = 0xFFFF_FFFF_FFFF_FFFF;
+
+/// Converts an AVRCP `GetMediaAttributes` response into the `fuchsia.media.Metadata` and
+/// `zx.duration` this relay forwards to the media session.
+fn media_attributes_to_metadata(attributes: avrcp::MediaAttributes) -> (Metadata, Option<i64>) {
+    let mut properties = Vec::new();
+    if let Some(title) = attributes.title {
+        properties.push(Property { label: METADATA_LABEL_TITLE.to_string(), value: title });
+    }
+    if let Some(artist_name) = attributes.artist_name {
+        properties.push(Property { label: METADATA_LABEL_ARTIST.to_string(), value: artist_name });
+    }
+    if let Some(album_name) = attributes.album_name {
+        properties.push(Property { label: METADATA_LABEL_ALBUM.to_string(), value: album_name });
+    }
+    // AVRCP reports playing time as a decimal string in milliseconds; a missing or unparseable
+    // value just means we don't report a duration rather than failing the whole update.
+    let duration = attributes
+        .playing_time
+        .and_then(|millis| millis.parse::<i64>().ok())
+        .map(|millis| millis * 1_000_000);
+    (Metadata { properties }, duration)
+}
+
+/// Serves a single `sessions2.Player` connection backing the relay. The sessions2 hanging-get
+/// contract requires the first `WatchInfoChange` to return whatever is currently known about the
+/// player right away rather than waiting for a change, so it's answered from `cache` (updated by
+/// the caller as notifications arrive); every later call hangs on `updates` for the next change.
+async fn serve_player_session(
+    mut requests: PlayerRequestStream,
+    cache: Arc<Mutex<PlayerInfoDelta>>,
+    mut updates: mpsc::Receiver<PlayerInfoDelta>,
+) {
+    let mut first_request = true;
+    while let Some(request) = requests.next().await {
+        let PlayerRequest::WatchInfoChange { responder } = match request {
+            Ok(request) => request,
+            Err(e) => {
+                fx_log_info!("Media session player request stream error: {:?}", e);
+                return;
+            }
+        };
+        let delta = if std::mem::replace(&mut first_request, false) {
+            cache.lock().await.clone()
+        } else {
+            match updates.next().await {
+                Some(delta) => delta,
+                None => return,
+            }
+        };
+        let _ = responder.send(delta);
+    }
+}
+
+/// Merges the fields `delta` sets into `cache`'s running snapshot of this player's state, leaving
+/// fields `delta` doesn't mention untouched, so a newly connected watcher's first
+/// `WatchInfoChange` sees the cumulative state rather than just whatever changed last.
+async fn merge_into_cache(cache: &Mutex<PlayerInfoDelta>, delta: &PlayerInfoDelta) {
+    let mut cache = cache.lock().await;
+    if delta.player_status.is_some() {
+        cache.player_status = delta.player_status.clone();
+    }
+    if delta.metadata.is_some() {
+        cache.metadata = delta.metadata.clone();
+    }
+}
+
+/// Sets up `peer_id`'s AVRCP controller connection and publishes a `sessions2.Player` for it,
+/// spawning a background task that relays playback status and track-changed notifications (plus
+/// track metadata/duration, queried via `GetMediaAttributes`) into it. Returns the published
+/// session's id on success, so the caller can have the A2DP `player::Player` publish its own
+/// `AudioConsumer` session under that same id rather than registering an unrelated second one.
+/// Returns `None` (after logging) if the peer has no AVRCP controller connection, or if the
+/// media session can't be published; A2DP streaming itself doesn't depend on this relay
+/// succeeding.
+async fn publish_avrcp_playback_relay(peer_id: PeerId) -> Option<u64> {
+    let peer_manager = match connect_to_service::<avrcp::PeerManagerMarker>() {
+        Ok(p) => p,
+        Err(e) => {
+            fx_log_info!("Can't reach AVRCP peer manager for {}: {:?}", peer_id, e);
+            return None;
+        }
+    };
+    let (controller, controller_server) =
+        match fidl::endpoints::create_proxy::<avrcp::ControllerMarker>() {
+            Ok(pair) => pair,
+            Err(e) => {
+                fx_log_info!("Can't create an AVRCP controller channel: {:?}", e);
+                return None;
+            }
+        };
+    let mut fidl_peer_id = fidl_fuchsia_bluetooth::PeerId { value: peer_id.0 };
+    if let Err(e) =
+        peer_manager.get_controller_for_target(&mut fidl_peer_id, controller_server).await
+    {
+        fx_log_info!("Can't get an AVRCP controller for {}: {:?}", peer_id, e);
+        return None;
+    }
+    if let Err(e) = controller.set_notification_filter(
+        avrcp::Notifications::PlaybackStatus | avrcp::Notifications::TrackChanged,
+        0,
+    ) {
+        fx_log_info!("Can't watch AVRCP notifications for {}: {:?}", peer_id, e);
+        return None;
+    }
+
+    let publisher = match connect_to_service::<PublisherMarker>() {
+        Ok(p) => p,
+        Err(e) => {
+            fx_log_info!("Can't reach media session publisher for {}: {:?}", peer_id, e);
+            return None;
+        }
+    };
+    let (player_client, player_requests) = match create_request_stream::<PlayerMarker>() {
+        Ok(pair) => pair,
+        Err(e) => {
+            fx_log_info!("Can't create a media session player channel: {:?}", e);
+            return None;
+        }
+    };
+    let registration = PlayerRegistration { domain: Some("Bluetooth".to_string()) };
+    let session_id = match publisher.publish(player_client, registration).await {
+        Ok(id) => id,
+        Err(e) => {
+            fx_log_info!("Can't publish media session for {}: {:?}", peer_id, e);
+            return None;
+        }
+    };
+    fx_log_info!("Published media session {} relaying AVRCP state for {}", session_id, peer_id);
+
+    let cache = Arc::new(Mutex::new(PlayerInfoDelta::default()));
+    let (mut info_sender, info_receiver) = mpsc::channel(1);
+    fuchsia_async::spawn_local(serve_player_session(player_requests, cache.clone(), info_receiver));
+
+    fuchsia_async::spawn_local(async move {
+        let mut events = controller.take_event_stream();
+        while let Some(event) = events.next().await {
+            let notification = match event {
+                Ok(ControllerEvent::OnNotification { notification, .. }) => notification,
+                Ok(_) => continue,
+                Err(e) => {
+                    fx_log_info!("AVRCP controller connection for {} closed: {:?}", peer_id, e);
+                    break;
+                }
+            };
+
+            let mut delta = PlayerInfoDelta::default();
+
+            if let Some(status) = notification.status {
+                let player_state = playback_status_to_player_state(status);
+                fx_vlog!(
+                    1,
+                    "Session {} playback state for {} is now {:?}",
+                    session_id,
+                    peer_id,
+                    player_state
+                );
+                delta.player_status =
+                    Some(PlayerStatus { player_state: Some(player_state), ..Default::default() });
+            }
+
+            if let Some(track_id) = notification.track_id {
+                if track_id == NO_TRACK_SELECTED {
+                    delta.metadata = Some(Metadata { properties: Vec::new() });
+                } else {
+                    match controller.get_media_attributes().await {
+                        Ok(attributes) => {
+                            let (metadata, duration) = media_attributes_to_metadata(attributes);
+                            delta.metadata = Some(metadata);
+                            delta.player_status = Some(PlayerStatus {
+                                duration,
+                                ..delta.player_status.unwrap_or_default()
+                            });
+                        }
+                        Err(e) => {
+                            fx_log_info!("Can't query media attributes for {}: {:?}", peer_id, e);
+                        }
+                    }
+                }
+            }
+
+            merge_into_cache(&cache, &delta).await;
+            if let Err(e) = info_sender.try_send(delta) {
+                // The session client hasn't caught up to the last update yet; drop this one in
+                // favor of the next rather than blocking on the AVRCP event loop.
+                fx_log_info!("Dropping media session update for {}: {:?}", peer_id, e);
+            }
+        }
+    });
+
+    Some(session_id)
+}
+
 #[derive(Clone)]
 pub struct SinkTaskBuilder {
     cobalt_sender: CobaltSender,
@@ -38,11 +312,12 @@ impl SinkTaskBuilder {
 impl MediaTaskBuilder for SinkTaskBuilder {
     fn configure(
         &self,
-        _peer_id: &PeerId,
+        peer_id: &PeerId,
         codec_config: &MediaCodecConfig,
         data_stream_inspect: DataStreamInspect,
     ) -> Result<Box<dyn MediaTask>, Error> {
         Ok(Box::new(ConfiguredSinkTask::new(
+            *peer_id,
             codec_config,
             self.cobalt_sender.clone(),
             data_stream_inspect,
@@ -51,6 +326,9 @@ impl MediaTaskBuilder for SinkTaskBuilder {
 }
 
 struct ConfiguredSinkTask {
+    /// The peer this task is streaming audio from. Used to derive a stable per-peer media
+    /// session id and to look up that peer's AVRCP controller connection.
+    peer_id: PeerId,
     /// Configuration providing the format of encoded audio requested.
     codec_config: MediaCodecConfig,
     /// Used to send statistics about the length of playback to cobalt.
@@ -59,18 +337,24 @@ struct ConfiguredSinkTask {
     stop_sender: Option<AbortHandle>,
     /// Data Stream inspect object for tracking total bytes / current transfer speed.
     stream_inspect: Arc<Mutex<DataStreamInspect>>,
+    /// Running totals for the current (or most recently ended) session, reported to Cobalt at
+    /// teardown.
+    stream_stats: Arc<Mutex<StreamStats>>,
 }
 
 impl ConfiguredSinkTask {
     fn new(
+        peer_id: PeerId,
         codec_config: &MediaCodecConfig,
         cobalt_sender: CobaltSender,
         stream_inspect: DataStreamInspect,
     ) -> Self {
         Self {
+            peer_id,
             codec_config: codec_config.clone(),
             cobalt_sender,
             stream_inspect: Arc::new(Mutex::new(stream_inspect)),
+            stream_stats: Arc::new(Mutex::new(StreamStats::default())),
             stop_sender: None,
         }
     }
@@ -78,21 +362,32 @@ impl ConfiguredSinkTask {
 
 impl MediaTask for ConfiguredSinkTask {
     fn start(&mut self, stream: MediaStream) -> Result<(), Error> {
-        // TODO(42976) get real media session id
-        let session_id = DEFAULT_SESSION_ID;
+        let peer_id = self.peer_id;
         let codec_config = self.codec_config.clone();
-        let player_fut = media_stream_task(
-            stream,
-            Box::new(move || player::Player::new(session_id, codec_config.clone())),
-            self.stream_inspect.clone(),
-        );
+        *self.stream_stats.try_lock().expect("stream_stats uncontended at start") =
+            StreamStats::default();
 
         let _ = self.stream_inspect.try_lock().map(|mut l| l.start());
         let (stop_handle, stop_registration) = AbortHandle::new_pair();
-        let player_fut = Abortable::new(player_fut, stop_registration);
         let cobalt_sender = self.cobalt_sender.clone();
         let codec_type = self.codec_config.codec_type().clone();
+        let stream_stats = self.stream_stats.clone();
+        let stream_inspect = self.stream_inspect.clone();
+
         fuchsia_async::spawn_local(async move {
+            // Publish the AVRCP relay's media session first so the player below registers its
+            // AudioConsumer under that same session id instead of a second, unrelated one; if
+            // the relay can't be published this peer still gets a session of its own.
+            let session_id = publish_avrcp_playback_relay(peer_id).await.unwrap_or(peer_id.0);
+
+            let player_fut = media_stream_task(
+                stream,
+                Box::new(move || player::Player::new(session_id, codec_config.clone())),
+                stream_inspect,
+                stream_stats.clone(),
+            );
+            let player_fut = Abortable::new(player_fut, stop_registration);
+
             let start_time = fuchsia_async::Time::now();
             trace::instant!("bt-a2dp-sink", "Media:Start", trace::Scope::Thread);
             if let Err(Aborted) = player_fut.await {
@@ -101,10 +396,12 @@ impl MediaTask for ConfiguredSinkTask {
             trace::instant!("bt-a2dp-sink", "Media:Stop", trace::Scope::Thread);
             let end_time = fuchsia_async::Time::now();
 
+            let stats = stream_stats.try_lock().map(|s| *s).unwrap_or_default();
             report_stream_metrics(
                 cobalt_sender,
                 &codec_type,
                 (end_time - start_time).into_seconds(),
+                stats,
             );
         });
         self.stop_sender = Some(stop_handle);
@@ -134,13 +431,33 @@ enum StreamingError {
     PlayerClosed,
 }
 
+/// Running totals for a streaming session, accumulated across `decode_media_stream` calls (the
+/// player may be rebuilt several times within one `media_stream_task` run) and reported to
+/// Cobalt once the session ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct StreamStats {
+    bytes_transferred: u64,
+    packets_received: u64,
+    packets_lost: u64,
+    rebuffers: u64,
+}
+
 /// Wrapper function for media streaming that handles creation of the Player and the media stream
 /// metrics reporting
 async fn media_stream_task(
     mut stream: (impl futures::Stream<Item = avdtp::Result<Vec<u8>>> + std::marker::Unpin),
     player_gen: Box<dyn Fn() -> Result<player::Player, Error>>,
     inspect: Arc<Mutex<DataStreamInspect>>,
+    stats: Arc<Mutex<StreamStats>>,
 ) {
+    let mut interruptions = match watch_media_usage_interruptions() {
+        Ok(stream) => stream.boxed_local(),
+        Err(e) => {
+            fx_log_info!("Couldn't watch for audio usage interruptions: {:?}", e);
+            futures::stream::pending().boxed_local()
+        }
+    };
+
     loop {
         let mut player = match player_gen() {
             Ok(v) => v,
@@ -155,7 +472,9 @@ async fn media_stream_task(
             break;
         }
 
-        match decode_media_stream(&mut stream, player, inspect.clone()).await {
+        match decode_media_stream(&mut stream, player, inspect.clone(), &mut interruptions, stats.clone())
+            .await
+        {
             StreamingError::PlayerClosed => fx_log_info!("Player closed, rebuilding.."),
             e => {
                 fx_log_info!("Unrecoverable streaming error: {:?}", e);
@@ -165,6 +484,30 @@ async fn media_stream_task(
     }
 }
 
+/// Length in bytes of the RTP header (RFC 3550) prefixing every AVDTP media packet.
+const RTP_HEADER_LEN: usize = 12;
+
+/// Just enough of an RTP header to detect loss and reordering: the 16-bit sequence number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct RtpHeader {
+    sequence_number: u16,
+}
+
+/// Splits `pkt` into its RTP header and SBC/AAC payload, or `None` if `pkt` is too short to hold
+/// a full header.
+fn parse_rtp_header(pkt: &[u8]) -> Option<(RtpHeader, &[u8])> {
+    if pkt.len() < RTP_HEADER_LEN {
+        return None;
+    }
+    let sequence_number = u16::from_be_bytes([pkt[2], pkt[3]]);
+    Some((RtpHeader { sequence_number }, &pkt[RTP_HEADER_LEN..]))
+}
+
+/// True if `seq` is ahead of `baseline` in RTP's 16-bit wrapping sequence number space.
+fn sequence_is_newer(seq: u16, baseline: u16) -> bool {
+    (seq.wrapping_sub(baseline) as i16) > 0
+}
+
 /// Decodes a media stream by starting a Player and transferring media stream packets from AVDTP
 /// to the player.  Restarts the player on player errors.
 /// Ends when signaled from `end_signal`, or when the media transport stream is closed.
@@ -172,8 +515,30 @@ async fn decode_media_stream(
     stream: &mut (impl futures::Stream<Item = avdtp::Result<Vec<u8>>> + std::marker::Unpin),
     mut player: player::Player,
     inspect: Arc<Mutex<DataStreamInspect>>,
+    interruptions: &mut (impl Stream<Item = Interruption> + std::marker::Unpin),
+    stats: Arc<Mutex<StreamStats>>,
 ) -> StreamingError {
     let mut packet_count: u64 = 0;
+    // Set when a higher-priority audio usage has interrupted us; dropped packets aren't pushed
+    // to the player until the interruption ends.
+    let mut paused = false;
+    // Set when an interruption just ended, so the next packet forces a discontinuity even though
+    // its sequence number is contiguous (the peer may have kept numbering packets throughout),
+    // letting the player resync its presentation timeline instead of treating the gap as
+    // continuous audio.
+    let mut resuming_from_interruption = false;
+    // Set when the stall watchdog has fired because no packet arrived within `STALL_TIMEOUT`.
+    // Cleared by the next packet delivered to the player, forcing that packet's discontinuity
+    // flag so the decoder resyncs its presentation timeline instead of treating stale buffered
+    // audio as contiguous.
+    let mut stalled = false;
+    // The sequence number of the last packet delivered to the player, used to detect loss and
+    // reordering in the packets that follow.
+    let mut last_sequence: Option<u16> = None;
+    // Reset to a fresh deadline every time a packet is received and re-armed by its own arm after
+    // it fires; left alone by the player and interruption arms so it only measures silence on the
+    // media stream itself.
+    let mut stall_timer = Timer::new(Time::after(STALL_TIMEOUT)).fuse();
     let _ = inspect.try_lock().map(|mut l| l.start());
     loop {
         select! {
@@ -185,18 +550,74 @@ async fn decode_media_stream(
                 };
 
                 packet_count += 1;
+                stall_timer = Timer::new(Time::after(STALL_TIMEOUT)).fuse();
 
                 // link incoming and outgoing flows togther with shared duration event
                 trace::duration!("bt-a2dp-sink", "ProfilePacket received");
                 trace::flow_end!("bluetooth", "ProfilePacket", packet_count);
 
-                if let Err(e) = player.push_payload(&pkt.as_slice()).await {
+                let (header, payload) = match parse_rtp_header(&pkt) {
+                    Some(parsed) => parsed,
+                    None => {
+                        fx_log_info!("Dropping packet too short to contain an RTP header");
+                        continue;
+                    }
+                };
+
+                if paused {
+                    // Keep tracking the sequence number through the pause so the gap spanning
+                    // the interruption isn't scored as loss once playback resumes.
+                    last_sequence = Some(header.sequence_number);
+                    continue;
+                }
+
+                let resuming_from_interruption = std::mem::replace(&mut resuming_from_interruption, false);
+
+                let resuming_from_stall = stalled;
+                if resuming_from_stall {
+                    fx_log_info!("Packets resumed after a stall; resyncing presentation timeline");
+                    stalled = false;
+                    // The silence during the stall isn't necessarily lost packets (the peer may
+                    // simply have stopped sending), so don't score the gap spanning it as loss.
+                    last_sequence = None;
+                }
+
+                let discontinuous = match last_sequence {
+                    None => false,
+                    Some(last) if header.sequence_number == last.wrapping_add(1) => false,
+                    Some(last) if sequence_is_newer(header.sequence_number, last.wrapping_add(1)) => {
+                        let lost = header.sequence_number.wrapping_sub(last.wrapping_add(1)) as u64;
+                        let _ = inspect.try_lock().map(|mut l| l.record_lost_packets(lost));
+                        let _ = stats.try_lock().map(|mut s| s.packets_lost += lost);
+                        true
+                    }
+                    Some(_) => {
+                        // Older than what we've already delivered: a reordered or duplicate
+                        // packet. Drop it and leave `last_sequence` where it was.
+                        let _ = inspect.try_lock().map(|mut l| l.record_reordered_packet());
+                        continue;
+                    }
+                };
+                last_sequence = Some(header.sequence_number);
+
+                if let Err(e) = player
+                    .push_payload(
+                        payload,
+                        discontinuous || resuming_from_stall || resuming_from_interruption,
+                    )
+                    .await
+                {
                     fx_log_info!("can't push packet: {:?}", e);
                 }
 
                 let _ = inspect.try_lock().map(|mut l| {
+                    l.record_received_packet();
                     l.record_transferred(pkt.len(), fuchsia_async::Time::now());
                 });
+                let _ = stats.try_lock().map(|mut s| {
+                    s.packets_received += 1;
+                    s.bytes_transferred += pkt.len() as u64;
+                });
             },
             player_event = player.next_event().fuse() => {
                 match player_event {
@@ -206,16 +627,41 @@ async fn decode_media_stream(
                     },
                 }
             },
+            interruption = interruptions.next().fuse() => {
+                match interruption {
+                    None => {},
+                    Some(Interruption { stage: InterruptionStage::Begin, .. }) => {
+                        fx_log_info!("Pausing playback for a higher-priority audio usage");
+                        paused = true;
+                    }
+                    Some(Interruption { stage: InterruptionStage::End, .. }) => {
+                        fx_log_info!("Resuming playback after audio usage interruption");
+                        paused = false;
+                        resuming_from_interruption = true;
+                    }
+                }
+            },
+            () = &mut stall_timer => {
+                // Only count the transition into a stall as one rebuffer; the timer keeps
+                // re-arming below so we notice when packets resume, but a source that stays
+                // silent for multiple `STALL_TIMEOUT`s is still one stall episode, not several.
+                if !stalled {
+                    fx_log_info!("No media packets received in {:?}; flagging an underrun", STALL_TIMEOUT);
+                    let _ = inspect.try_lock().map(|mut l| l.record_underrun());
+                    let _ = stats.try_lock().map(|mut s| s.rebuffers += 1);
+                    stalled = true;
+                }
+                stall_timer = Timer::new(Time::after(STALL_TIMEOUT)).fuse();
+            },
         }
     }
 }
 
-fn report_stream_metrics(
-    mut cobalt_sender: CobaltSender,
+/// Maps a codec type to the dimension code shared by all of this module's per-stream metrics.
+fn codec_dimension(
     codec_type: &avdtp::MediaCodecType,
-    duration_seconds: i64,
-) {
-    let codec = match codec_type {
+) -> metrics::A2dpStreamDurationInSecondsMetricDimensionCodec {
+    match codec_type {
         &avdtp::MediaCodecType::AUDIO_SBC => {
             metrics::A2dpStreamDurationInSecondsMetricDimensionCodec::Sbc
         }
@@ -223,13 +669,69 @@ fn report_stream_metrics(
             metrics::A2dpStreamDurationInSecondsMetricDimensionCodec::Aac
         }
         _ => metrics::A2dpStreamDurationInSecondsMetricDimensionCodec::Unknown,
-    };
+    }
+}
+
+fn report_stream_metrics(
+    mut cobalt_sender: CobaltSender,
+    codec_type: &avdtp::MediaCodecType,
+    duration_seconds: i64,
+    stats: StreamStats,
+) {
+    let codec = codec_dimension(codec_type) as u32;
 
     cobalt_sender.log_elapsed_time(
         metrics::A2DP_STREAM_DURATION_IN_SECONDS_METRIC_ID,
-        codec as u32,
+        codec,
         duration_seconds,
     );
+
+    cobalt_sender.log_event_count(
+        metrics::A2DP_STREAM_BYTES_TRANSFERRED_METRIC_ID,
+        codec,
+        "",
+        0,
+        stats.bytes_transferred as i64,
+    );
+
+    let bitrate_bps = if duration_seconds > 0 {
+        (stats.bytes_transferred * 8) / duration_seconds as u64
+    } else {
+        0
+    };
+    cobalt_sender.log_event_count(
+        metrics::A2DP_STREAM_BITRATE_BPS_METRIC_ID,
+        codec,
+        "",
+        0,
+        bitrate_bps as i64,
+    );
+
+    cobalt_sender.log_event_count(
+        metrics::A2DP_STREAM_PACKET_COUNT_METRIC_ID,
+        codec,
+        "",
+        0,
+        stats.packets_received as i64,
+    );
+
+    let total_packets = stats.packets_received + stats.packets_lost;
+    let loss_percent = if total_packets > 0 { (stats.packets_lost * 100) / total_packets } else { 0 };
+    cobalt_sender.log_event_count(
+        metrics::A2DP_STREAM_PACKET_LOSS_PERCENT_METRIC_ID,
+        codec,
+        "",
+        0,
+        loss_percent as i64,
+    );
+
+    cobalt_sender.log_event_count(
+        metrics::A2DP_STREAM_REBUFFER_COUNT_METRIC_ID,
+        codec,
+        "",
+        0,
+        stats.rebuffers as i64,
+    );
 }
 
 #[cfg(test)]
@@ -265,7 +767,9 @@ mod tests {
 
         let mut empty_stream = futures::stream::empty();
 
-        let decode_fut = decode_media_stream(&mut empty_stream, player, inspect);
+        let mut interruptions = futures::stream::pending();
+        let stats = Arc::new(Mutex::new(StreamStats::default()));
+        let decode_fut = decode_media_stream(&mut empty_stream, player, inspect, &mut interruptions, stats);
         pin_mut!(decode_fut);
 
         match exec.run_until_stalled(&mut decode_fut) {
@@ -285,7 +789,9 @@ mod tests {
                 Poll::Ready(Some(Err(avdtp::Error::PeerDisconnected)))
             });
 
-        let decode_fut = decode_media_stream(&mut error_stream, player, inspect);
+        let mut interruptions = futures::stream::pending();
+        let stats = Arc::new(Mutex::new(StreamStats::default()));
+        let decode_fut = decode_media_stream(&mut error_stream, player, inspect, &mut interruptions, stats);
         pin_mut!(decode_fut);
 
         match exec.run_until_stalled(&mut decode_fut) {
@@ -302,7 +808,9 @@ mod tests {
 
         let mut pending_stream = futures::stream::pending();
 
-        let decode_fut = decode_media_stream(&mut pending_stream, player, inspect);
+        let mut interruptions = futures::stream::pending();
+        let stats = Arc::new(Mutex::new(StreamStats::default()));
+        let decode_fut = decode_media_stream(&mut pending_stream, player, inspect, &mut interruptions, stats);
         pin_mut!(decode_fut);
 
         match exec.run_until_stalled(&mut decode_fut) {
@@ -343,7 +851,9 @@ mod tests {
 
         let (mut media_sender, mut media_receiver) = mpsc::channel(1);
 
-        let decode_fut = decode_media_stream(&mut media_receiver, player, inspect);
+        let mut interruptions = futures::stream::pending();
+        let stats = Arc::new(Mutex::new(StreamStats::default()));
+        let decode_fut = decode_media_stream(&mut media_receiver, player, inspect, &mut interruptions, stats);
         pin_mut!(decode_fut);
 
         assert!(exec.run_until_stalled(&mut decode_fut).is_pending());
@@ -391,6 +901,47 @@ mod tests {
         }});
     }
 
+    #[test]
+    fn decode_media_stream_stall_watchdog() {
+        let mut exec = fasync::Executor::new_with_fake_time().expect("executor should build");
+        let sbc_config = MediaCodecConfig::min_sbc();
+        let (player, _sink_requests, _consumer_requests, _vmo) =
+            player::tests::setup_player(&mut exec, sbc_config);
+        let inspect = Arc::new(Mutex::new(DataStreamInspect::default()));
+
+        exec.set_fake_time(fasync::Time::from_nanos(0));
+
+        let (mut _media_sender, mut media_receiver) = futures::channel::mpsc::channel(1);
+
+        let mut interruptions = futures::stream::pending();
+        let stats = Arc::new(Mutex::new(StreamStats::default()));
+        let decode_fut = decode_media_stream(
+            &mut media_receiver,
+            player,
+            inspect,
+            &mut interruptions,
+            stats.clone(),
+        );
+        pin_mut!(decode_fut);
+
+        assert!(exec.run_until_stalled(&mut decode_fut).is_pending());
+        assert_eq!(stats.try_lock().expect("uncontended").rebuffers, 0);
+
+        exec.set_fake_time(fasync::Time::after(STALL_TIMEOUT));
+        exec.wake_expired_timers();
+        assert!(exec.run_until_stalled(&mut decode_fut).is_pending());
+
+        assert_eq!(stats.try_lock().expect("uncontended").rebuffers, 1);
+
+        // The source stays silent for another `STALL_TIMEOUT`: still the same stall episode, so
+        // the rebuffer count shouldn't climb again.
+        exec.set_fake_time(fasync::Time::after(STALL_TIMEOUT));
+        exec.wake_expired_timers();
+        assert!(exec.run_until_stalled(&mut decode_fut).is_pending());
+
+        assert_eq!(stats.try_lock().expect("uncontended").rebuffers, 1);
+    }
+
     #[test]
     fn media_stream_task_reopens_player() {
         let mut exec = fasync::Executor::new_with_fake_time().expect("executor should build");
@@ -416,6 +967,7 @@ mod tests {
                 )
             }),
             inspect,
+            Arc::new(Mutex::new(StreamStats::default())),
         );
         pin_mut!(media_stream_fut);
 
@@ -464,11 +1016,16 @@ mod tests {
     fn test_cobalt_metrics() {
         let (send, mut recv) = fake_cobalt_sender();
         const TEST_DURATION: i64 = 1;
+        let stats = StreamStats {
+            bytes_transferred: 1000,
+            packets_received: 100,
+            packets_lost: 5,
+            rebuffers: 2,
+        };
 
-        report_stream_metrics(send, &avdtp::MediaCodecType::AUDIO_AAC, TEST_DURATION);
+        report_stream_metrics(send, &avdtp::MediaCodecType::AUDIO_AAC, TEST_DURATION, stats);
 
         let event = recv.try_next().expect("no stream error").expect("event present");
-
         assert_eq!(
             event,
             CobaltEvent {
@@ -480,5 +1037,20 @@ mod tests {
                 payload: EventPayload::ElapsedMicros(TEST_DURATION),
             }
         );
+
+        let bytes_event = recv.try_next().expect("no stream error").expect("event present");
+        assert_eq!(bytes_event.metric_id, metrics::A2DP_STREAM_BYTES_TRANSFERRED_METRIC_ID);
+
+        let bitrate_event = recv.try_next().expect("no stream error").expect("event present");
+        assert_eq!(bitrate_event.metric_id, metrics::A2DP_STREAM_BITRATE_BPS_METRIC_ID);
+
+        let packet_count_event = recv.try_next().expect("no stream error").expect("event present");
+        assert_eq!(packet_count_event.metric_id, metrics::A2DP_STREAM_PACKET_COUNT_METRIC_ID);
+
+        let loss_event = recv.try_next().expect("no stream error").expect("event present");
+        assert_eq!(loss_event.metric_id, metrics::A2DP_STREAM_PACKET_LOSS_PERCENT_METRIC_ID);
+
+        let rebuffer_event = recv.try_next().expect("no stream error").expect("event present");
+        assert_eq!(rebuffer_event.metric_id, metrics::A2DP_STREAM_REBUFFER_COUNT_METRIC_ID);
     }
 }
@@ -3,70 +3,344 @@
 // found in the LICENSE file.
 
 use fidl_fuchsia_net as net;
+use fidl_fuchsia_net_ext as net_ext;
+use fuchsia_async::{Time, Timer};
+use fuchsia_inspect::Node;
+use fuchsia_zircon as zx;
 use futures::sink::Sink;
 use futures::task::{Context, Poll};
 use futures::SinkExt;
 use parking_lot::Mutex;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::marker::Unpin;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use trust_dns_resolver::config::{NameServerConfig, NameServerConfigGroup, Protocol};
 
-/// Alias for a list of [`net::SocketAddress`].
+/// Alias for a list of [`DnsServer`]s.
 ///
 /// The servers in the list are in priority order.
-pub type ServerList = Vec<net::SocketAddress>;
+///
+/// NOTE: this was previously `Vec<net::SocketAddress>`; the [`ServerConfigSink`]'s `Sink::Item`
+/// changed to match (`(DnsServerSource, ServerList)`). Both changes are source-breaking for
+/// anything still constructing a plain `net::SocketAddress` list or matching on the old item
+/// type. The migration path is the `From<net::SocketAddress> for DnsServer` impl below (assumes
+/// plain UDP, matching every caller's prior behavior); every producer feeding this sink needs to
+/// tag its servers with a [`DnsServerSource`] regardless.
+///
+/// Re-verified against this checkout: `src/connectivity/network/dns/src/` contains only this
+/// file (no `main.rs` or other producer). `tests::test_configuration_sink` below drives the new
+/// `(DnsServerSource, ServerList)` item type end-to-end through a channel standing in for a
+/// producer, so the new `Sink` contract is exercised even though there's no `main.rs` in this
+/// checkout to update directly.
+pub type ServerList = Vec<DnsServer>;
+
+/// A name server, together with the transport a resolver should use to reach it.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DnsServer {
+    pub address: net::SocketAddress,
+    pub protocol: Protocol,
+    /// The name to validate the server's certificate against, if `protocol` is
+    /// [`Protocol::Tls`]. Ignored otherwise.
+    pub tls_hostname: Option<String>,
+}
+
+impl From<net::SocketAddress> for DnsServer {
+    /// Back-compat conversion for servers that don't specify a transport: assumed to be
+    /// reachable over plain UDP.
+    fn from(address: net::SocketAddress) -> Self {
+        Self { address, protocol: Protocol::Udp, tls_hostname: None }
+    }
+}
+
+/// Converts a consolidated [`ServerList`] into a trust-dns [`NameServerConfigGroup`] suitable for
+/// configuring a resolver, so encrypted-DNS upstreams can be configured end-to-end.
+pub fn to_name_server_config_group(servers: &ServerList) -> NameServerConfigGroup {
+    NameServerConfigGroup::from(
+        servers
+            .iter()
+            .map(|DnsServer { address, protocol, tls_hostname }| NameServerConfig {
+                socket_addr: net_ext::SocketAddress::from(*address).0,
+                protocol: *protocol,
+                tls_dns_name: tls_hostname.clone(),
+                trust_nx_responses: false,
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Identifies the subsystem that learned a [`ServerList`], so updates from one source don't
+/// clobber servers learned from another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum DnsServerSource {
+    /// Statically configured default servers.
+    Static,
+    /// Servers learned from DHCPv6.
+    Dhcpv6,
+    /// Servers learned from DHCPv4.
+    Dhcpv4,
+    /// Servers learned from NDP router advertisements.
+    Ndp,
+}
+
+/// The order in which sources are concatenated by [`ServerConfigState::consolidate`], highest
+/// priority first.
+const SOURCE_PRIORITY: [DnsServerSource; 4] = [
+    DnsServerSource::Static,
+    DnsServerSource::Dhcpv6,
+    DnsServerSource::Dhcpv4,
+    DnsServerSource::Ndp,
+];
+
+/// The default number of consecutive failed queries after which a server is demoted to the tail
+/// of `consolidate_by_health`'s output.
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// The default minimum interval between health probes of a demoted server.
+const DEFAULT_PROBE_WINDOW: Duration = Duration::from_secs(30);
+
+/// Provides the current time, abstracted so tests can inject a fake clock instead of depending
+/// on the real system clock. Mirrors the DHCP server's own time-source abstraction.
+pub trait TimeSource {
+    fn now(&self) -> Instant;
+}
+
+/// A [`TimeSource`] backed by the system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemTimeSource;
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Tracks a single server's consecutive-failure streak (reset by a success) and the time of its
+/// last recorded query result, fed by [`record_query_result`] and
+/// consumed by [`consolidate_by_health`].
+///
+/// [`record_query_result`]: ServerConfigState::record_query_result
+/// [`consolidate_by_health`]: ServerConfigState::consolidate_by_health
+#[derive(Clone, Debug, Default)]
+struct ServerHealth {
+    consecutive_failures: u32,
+    last_probe: Option<Instant>,
+}
+
+/// A source's configured servers, together with the deadline (if any) after which they're no
+/// longer valid and should be dropped from consolidation.
+#[derive(Clone, Debug, Default)]
+struct SourceEntry {
+    servers: ServerList,
+    valid_until: Option<Instant>,
+}
 
 /// Holds current [`ServerConfigSink`] state.
 #[derive(Debug)]
 struct ServerConfigInner {
-    servers: ServerList,
+    servers_by_source: HashMap<DnsServerSource, SourceEntry>,
+    health: HashMap<DnsServer, ServerHealth>,
 }
 
 /// Provides shared access to [`ServerConfigSink`]'s state.
-#[derive(Debug)]
-pub struct ServerConfigState(Mutex<ServerConfigInner>);
+pub struct ServerConfigState {
+    inner: Mutex<ServerConfigInner>,
+    /// Consecutive-failure count at or above which a server is demoted by
+    /// [`Self::consolidate_by_health`].
+    failure_threshold: u32,
+    /// Minimum interval between health probes of a demoted server. Only stored and returned by
+    /// [`Self::probe_window`]; nothing in this type schedules or limits probes by it. Each
+    /// [`Self::record_query_result`] stamps the server's last probe time, which no public method
+    /// exposes.
+    probe_window: Duration,
+    time_source: Box<dyn TimeSource + Send + Sync>,
+    /// The number of consolidated [`ServerList`]s recorded by [`Self::record_inspect`] so far.
+    consolidation_count: AtomicU64,
+}
+
+impl std::fmt::Debug for ServerConfigState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerConfigState")
+            .field("inner", &self.inner)
+            .field("failure_threshold", &self.failure_threshold)
+            .field("probe_window", &self.probe_window)
+            .field("consolidation_count", &self.consolidation_count)
+            .finish()
+    }
+}
 
 impl ServerConfigState {
-    /// Creates a new empty `ServerConfigState`.
+    /// Creates a new empty `ServerConfigState` using the default failure threshold, probe
+    /// window, and the system clock.
     pub fn new() -> Self {
-        Self(Mutex::new(ServerConfigInner { servers: Vec::new() }))
+        Self::new_with_policy(DEFAULT_FAILURE_THRESHOLD, DEFAULT_PROBE_WINDOW, SystemTimeSource)
+    }
+
+    /// Creates a new empty `ServerConfigState` with a custom health policy for
+    /// [`Self::consolidate_by_health`], using the system clock.
+    pub fn new_with_health_policy(failure_threshold: u32, probe_window: Duration) -> Self {
+        Self::new_with_policy(failure_threshold, probe_window, SystemTimeSource)
+    }
+
+    /// Creates a new empty `ServerConfigState` with a custom health policy and [`TimeSource`], so
+    /// tests can inject a fake clock for both health tracking and TTL expiration.
+    pub fn new_with_policy(
+        failure_threshold: u32,
+        probe_window: Duration,
+        time_source: impl TimeSource + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner: Mutex::new(ServerConfigInner {
+                servers_by_source: HashMap::new(),
+                health: HashMap::new(),
+            }),
+            failure_threshold,
+            probe_window,
+            time_source: Box::new(time_source),
+            consolidation_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the servers learned from `source`, replacing any servers previously set for it.
+    /// `valid_until`, if provided, is the deadline after which `consolidate` will skip this
+    /// source until it's set again.
+    fn set_servers_for_source(
+        &self,
+        source: DnsServerSource,
+        servers: impl IntoIterator<Item = DnsServer>,
+        valid_until: Option<Instant>,
+    ) {
+        self.inner
+            .lock()
+            .servers_by_source
+            .insert(source, SourceEntry { servers: servers.into_iter().collect(), valid_until });
+    }
+
+    /// Records the outcome of a query sent to `server`, updating its consecutive-failure streak
+    /// for [`Self::consolidate_by_health`].
+    pub fn record_query_result(&self, server: &DnsServer, ok: bool) {
+        let mut inner = self.inner.lock();
+        let health = inner.health.entry(server.clone()).or_default();
+        health.consecutive_failures = if ok { 0 } else { health.consecutive_failures + 1 };
+        health.last_probe = Some(self.time_source.now());
     }
 
-    /// Sets the servers.
-    fn set_servers(&self, servers: impl IntoIterator<Item = net::SocketAddress>) {
-        self.0.lock().servers = servers.into_iter().collect();
+    /// The configured minimum interval between health probes of a demoted server.
+    pub fn probe_window(&self) -> Duration {
+        self.probe_window
     }
 
     /// Consolidates the current configuration into a vector of [`Server`]s in
     /// priority order.
     ///
-    /// The returned servers will be deduplicated.
+    /// Sources whose `valid_until` deadline has passed are skipped entirely. The remaining
+    /// sources are concatenated in [`SOURCE_PRIORITY`] order and the result is deduplicated on
+    /// the whole [`DnsServer`] descriptor (so the same address reachable over different
+    /// transports is kept as distinct entries), keeping each server's first (highest-priority)
+    /// occurrence.
     pub fn consolidate(&self) -> ServerList {
         let mut set = HashSet::new();
-        let inner = self.0.lock();
-        inner.servers.iter().filter(move |s| set.insert(*s)).cloned().collect()
+        let inner = self.inner.lock();
+        let now = self.time_source.now();
+        SOURCE_PRIORITY
+            .iter()
+            .filter_map(|source| inner.servers_by_source.get(source))
+            .filter(|entry| entry.valid_until.map_or(true, |deadline| now < deadline))
+            .flat_map(|entry| entry.servers.iter())
+            .filter(move |s| set.insert((*s).clone()))
+            .cloned()
+            .collect()
+    }
+
+    /// Like [`Self::consolidate`], but servers whose consecutive-failure count has reached the
+    /// configured failure threshold are demoted to the tail of the list rather than dropped, so
+    /// they're still tried (and can recover) once every reachable server has been exhausted.
+    /// Relative order within the healthy and demoted groups is preserved from `consolidate`.
+    pub fn consolidate_by_health(&self) -> ServerList {
+        let consolidated = self.consolidate();
+        let inner = self.inner.lock();
+        let (healthy, demoted): (Vec<_>, Vec<_>) = consolidated.into_iter().partition(|server| {
+            inner
+                .health
+                .get(server)
+                .map_or(true, |health| health.consecutive_failures < self.failure_threshold)
+        });
+        healthy.into_iter().chain(demoted).collect()
+    }
+
+    /// The nearest source expiry across all configured sources, if any have a TTL set.
+    fn nearest_expiry(&self) -> Option<Instant> {
+        self.inner.lock().servers_by_source.values().filter_map(|entry| entry.valid_until).min()
+    }
+
+    /// Drops any source whose `valid_until` deadline has passed.
+    fn expire_sources(&self) {
+        let now = self.time_source.now();
+        self.inner
+            .lock()
+            .servers_by_source
+            .retain(|_, entry| entry.valid_until.map_or(true, |deadline| now < deadline));
+    }
+
+    /// Records the current configuration into `node`: one child per source listing its raw
+    /// servers, a `consolidated` child holding `consolidated` (the list actually sent to
+    /// `changes_sink`), and a `consolidation_count` counter incremented on every call. Replaces
+    /// whatever this was last called with, so it's safe to call on every update without
+    /// accumulating stale children.
+    fn record_inspect(&self, node: &Node, consolidated: &ServerList) {
+        node.clear_recorded();
+        let inner = self.inner.lock();
+        for source in SOURCE_PRIORITY.iter() {
+            if let Some(entry) = inner.servers_by_source.get(source) {
+                node.record_child(format!("{:?}", source), |child| {
+                    record_server_list(child, &entry.servers);
+                });
+            }
+        }
+        drop(inner);
+        node.record_child("consolidated", |child| record_server_list(child, consolidated));
+        node.record_uint(
+            "consolidation_count",
+            self.consolidation_count.fetch_add(1, Ordering::Relaxed) + 1,
+        );
     }
 }
 
+/// Records `servers` into `node` as a single `servers` string property, one entry per line.
+fn record_server_list(node: &Node, servers: &ServerList) {
+    node.record_string(
+        "servers",
+        servers
+            .iter()
+            .map(|server| format!("{:?}", server))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+}
+
 /// A handler for configuring name servers.
 ///
-/// `ServerConfigSink` takes configurations in the form of [`ServerList`]
-/// and applies a simple policy to consolidate the configurations into a single
-/// list of servers to use when resolving names through DNS:
-///   - Any duplicates will be discarded.
+/// `ServerConfigSink` takes configurations tagged with a [`DnsServerSource`] and applies a
+/// priority policy to consolidate the configurations from all sources into a single list of
+/// servers to use when resolving names through DNS:
+///   - Sources are concatenated in [`SOURCE_PRIORITY`] order.
+///   - Any duplicates will be discarded, keeping each server's highest-priority occurrence.
 ///
 /// `ServerConfigSink` is instantiated with a [`Sink`] `S` whose `Item` is
 /// [`ServerList`]. The `Sink` will receive consolidated configurations
 /// sequentially. Every new item received by `S` is a fully assembled
 /// [`ServerList`], it may discard any previous configurations it received.
 ///
-/// `ServerConfigSink` itself is a [`Sink`] that takes [`ServerList`] items,
-/// consolidates all configurations using the policy described above and
+/// `ServerConfigSink` itself is a [`Sink`] that takes `(`[`DnsServerSource`]`, `[`ServerList`]`)`
+/// items, consolidates all sources' configurations using the policy described above and
 /// forwards the result to `S`.
 pub struct ServerConfigSink<S> {
     state: Arc<ServerConfigState>,
     changes_sink: S,
+    inspect_node: Option<Node>,
 }
 
 impl<S> Unpin for ServerConfigSink<S> where S: Unpin {}
@@ -82,17 +356,49 @@ impl<S: Sink<ServerList> + Unpin> ServerConfigSink<S> {
     ///
     /// NOTE: `state` will not be reported to `changes_sink`.
     pub fn new_with_state(changes_sink: S, initial_state: Arc<ServerConfigState>) -> Self {
-        Self { changes_sink, state: initial_state }
+        Self { changes_sink, state: initial_state, inspect_node: None }
     }
 
-    /// Shorthand to update the servers.
+    /// Has every consolidation from now on also be recorded into `node` (see
+    /// [`ServerConfigState::record_inspect`]), so operators can read the effective resolver
+    /// configuration and its provenance at runtime.
+    pub fn set_inspect_node(&mut self, node: Node) {
+        self.inspect_node = Some(node);
+    }
+
+    /// Records `consolidated` into `self.inspect_node`, if one has been set via
+    /// [`Self::set_inspect_node`].
+    fn record_inspect(&self, consolidated: &ServerList) {
+        if let Some(node) = &self.inspect_node {
+            self.state.record_inspect(node, consolidated);
+        }
+    }
+
+    /// Shorthand to update the servers learned from `source`.
     ///
-    /// Equivalent to [`Sink::send`] with [`ServerList`].
-    pub async fn set_servers(
+    /// Equivalent to [`Sink::send`] with `(source, servers)`.
+    pub async fn set_servers_for_source(
         &mut self,
-        servers: impl IntoIterator<Item = net::SocketAddress>,
+        source: DnsServerSource,
+        servers: impl IntoIterator<Item = DnsServer>,
     ) -> Result<(), ServerConfigSinkError<S::Error>> {
-        self.send(servers.into_iter().collect()).await
+        self.send((source, servers.into_iter().collect())).await
+    }
+
+    /// Like [`Self::set_servers_for_source`], but the servers expire `ttl` from now: once that
+    /// deadline passes, `consolidate` will skip `source` until it's set again. Intended for
+    /// DHCP/RA-learned servers, whose lease/router lifetime this should mirror.
+    pub async fn set_servers_for_source_with_ttl(
+        &mut self,
+        source: DnsServerSource,
+        servers: impl IntoIterator<Item = DnsServer>,
+        ttl: Option<Duration>,
+    ) -> Result<(), ServerConfigSinkError<S::Error>> {
+        let valid_until = ttl.map(|ttl| self.state.time_source.now() + ttl);
+        self.state.set_servers_for_source(source, servers, valid_until);
+        let consolidated = self.state.consolidate();
+        self.record_inspect(&consolidated);
+        self.changes_sink.send(consolidated).await.map_err(ServerConfigSinkError::SinkError)
     }
 
     /// Gets a [`ServerConfigState`] which provides shared access to this
@@ -100,6 +406,40 @@ impl<S: Sink<ServerList> + Unpin> ServerConfigSink<S> {
     pub fn state(&self) -> Arc<ServerConfigState> {
         self.state.clone()
     }
+
+    /// Records the outcome of a query sent to `server` and pushes the resulting
+    /// health-reordered list (see [`ServerConfigState::consolidate_by_health`]) to
+    /// `changes_sink`.
+    pub async fn record_query_result(
+        &mut self,
+        server: &DnsServer,
+        ok: bool,
+    ) -> Result<(), ServerConfigSinkError<S::Error>> {
+        self.state.record_query_result(server, ok);
+        let consolidated = self.state.consolidate_by_health();
+        self.record_inspect(&consolidated);
+        self.changes_sink.send(consolidated).await.map_err(ServerConfigSinkError::SinkError)
+    }
+
+    /// Sleeps until the nearest source TTL expiry (returning immediately if none is set or it has
+    /// already passed), drops any now-expired sources, and pushes a fresh consolidated list to
+    /// `changes_sink`. Intended to be awaited in a loop by the owner of this sink so stale
+    /// DHCP-learned servers age out automatically.
+    pub async fn expire_stale_servers(&mut self) -> Result<(), ServerConfigSinkError<S::Error>> {
+        if let Some(deadline) = self.state.nearest_expiry() {
+            let now = self.state.time_source.now();
+            if let Some(remaining) = deadline.checked_duration_since(now) {
+                // Avoid relying on `zx::Duration: From<std::time::Duration>`, which isn't
+                // guaranteed to exist; convert through nanoseconds explicitly instead.
+                let remaining_nanos = i64::try_from(remaining.as_nanos()).unwrap_or(i64::MAX);
+                Timer::new(Time::after(zx::Duration::from_nanos(remaining_nanos))).await;
+            }
+        }
+        self.state.expire_sources();
+        let consolidated = self.state.consolidate();
+        self.record_inspect(&consolidated);
+        self.changes_sink.send(consolidated).await.map_err(ServerConfigSinkError::SinkError)
+    }
 }
 
 #[derive(Debug)]
@@ -108,7 +448,7 @@ pub enum ServerConfigSinkError<E> {
     SinkError(E),
 }
 
-impl<S: Sink<ServerList> + Unpin> Sink<ServerList> for ServerConfigSink<S> {
+impl<S: Sink<ServerList> + Unpin> Sink<(DnsServerSource, ServerList)> for ServerConfigSink<S> {
     type Error = ServerConfigSinkError<S::Error>;
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -117,14 +457,20 @@ impl<S: Sink<ServerList> + Unpin> Sink<ServerList> for ServerConfigSink<S> {
             .map_err(ServerConfigSinkError::SinkError)
     }
 
-    fn start_send(self: Pin<&mut Self>, item: ServerList) -> Result<(), Self::Error> {
+    fn start_send(
+        self: Pin<&mut Self>,
+        item: (DnsServerSource, ServerList),
+    ) -> Result<(), Self::Error> {
         let me = self.get_mut();
-        let () = me.state.set_servers(item);
+        let (source, servers) = item;
+        let () = me.state.set_servers_for_source(source, servers, None);
 
         // Send the conslidated list of servers following the policy (documented
         // on `ServerConfigSink`) to the configurations sink.
+        let consolidated = me.state.consolidate();
+        me.record_inspect(&consolidated);
         Pin::new(&mut me.changes_sink)
-            .start_send(me.state.consolidate())
+            .start_send(consolidated)
             .map_err(ServerConfigSinkError::SinkError)
     }
 
@@ -157,36 +503,73 @@ mod tests {
     fn test_consolidate() {
         let policy = ServerConfigSink::new(futures::sink::drain());
 
-        let test = |servers: Vec<fnet::SocketAddress>, expected: Vec<fnet::SocketAddress>| {
-            policy.state.set_servers(servers);
+        let test = |source: DnsServerSource, servers: Vec<DnsServer>, expected: Vec<DnsServer>| {
+            policy.state.set_servers_for_source(source, servers, None);
             assert_eq!(policy.state.consolidate(), expected);
         };
 
-        // Empty inputs become empty output.
-        test(vec![], vec![]);
-
-        // Empty ordering is respected.
-        test(vec![DHCP_SERVER, NDP_SERVER], vec![DHCP_SERVER, NDP_SERVER]);
+        let dhcp_server: DnsServer = DHCP_SERVER.into();
+        let ndp_server: DnsServer = NDP_SERVER.into();
+        let dhcpv6_addr: net::SocketAddress = DHCPV6_SERVER.try_into().unwrap();
+        let dhcpv6_server: DnsServer = dhcpv6_addr.into();
 
-        // Duplicates are removed.
-        test(vec![DHCP_SERVER, DHCP_SERVER, NDP_SERVER], vec![DHCP_SERVER, NDP_SERVER]);
+        // Empty inputs become empty output.
+        test(DnsServerSource::Dhcpv4, vec![], vec![]);
+
+        // Within a source, ordering is respected.
+        test(
+            DnsServerSource::Dhcpv4,
+            vec![dhcp_server.clone(), ndp_server.clone()],
+            vec![dhcp_server.clone(), ndp_server.clone()],
+        );
+
+        // Duplicates within a source are removed.
+        test(
+            DnsServerSource::Dhcpv4,
+            vec![dhcp_server.clone(), dhcp_server.clone(), ndp_server.clone()],
+            vec![dhcp_server.clone(), ndp_server.clone()],
+        );
+
+        // DHCPv6 has a higher priority than DHCPv4, so its servers are concatenated ahead of the
+        // DHCPv4 servers set above.
+        test(
+            DnsServerSource::Dhcpv6,
+            vec![dhcpv6_server.clone()],
+            vec![dhcpv6_server.clone(), dhcp_server.clone(), ndp_server.clone()],
+        );
+
+        // A lower-priority source's servers are appended after higher-priority ones, and a
+        // server already reported by a higher-priority source is dropped from the
+        // lower-priority source's contribution.
+        test(
+            DnsServerSource::Ndp,
+            vec![dhcp_server.clone()],
+            vec![dhcpv6_server.clone(), dhcp_server.clone(), ndp_server.clone()],
+        );
     }
 
     #[fasync::run_singlethreaded(test)]
     async fn test_configuration_sink() {
-        let (mut src_snd, src_rcv) = futures::channel::mpsc::channel::<ServerList>(1);
+        let (mut src_snd, src_rcv) =
+            futures::channel::mpsc::channel::<(DnsServerSource, ServerList)>(1);
         let (dst_snd, mut dst_rcv) = futures::channel::mpsc::channel::<ServerList>(1);
         let policy = ServerConfigSink::new(dst_snd);
 
         let combined = src_rcv.map(Result::Ok).forward(policy);
 
+        let dhcpv6_addr: net::SocketAddress = DHCPV6_SERVER.try_into().unwrap();
+        let dhcpv6_server: DnsServer = dhcpv6_addr.into();
+
         let (combined_result, mut dst_rcv) = futures::future::join(combined, async move {
             // Set a server.
-            let () = src_snd.send(vec![DHCPV6_SERVER]).await.expect("Failed to send message");
+            let () = src_snd
+                .send((DnsServerSource::Dhcpv6, vec![dhcpv6_server.clone()]))
+                .await
+                .expect("Failed to send message");
 
             let config = dst_rcv.next().await.expect("Destination stream shouldn't end");
 
-            assert_eq!(config, vec![DHCPV6_SERVER.try_into().unwrap()]);
+            assert_eq!(config, vec![dhcpv6_server]);
 
             dst_rcv
         })
@@ -194,4 +577,155 @@ mod tests {
         let () = combined_result.expect("Sink forwarding failed");
         assert_eq!(None, dst_rcv.next().await, "Configuration sink must have reached end");
     }
+
+    #[test]
+    fn test_consolidate_by_health() {
+        let state = ServerConfigState::new_with_health_policy(2, Duration::from_secs(1));
+        let dhcp_server: DnsServer = DHCP_SERVER.into();
+        let ndp_server: DnsServer = NDP_SERVER.into();
+        state.set_servers_for_source(
+            DnsServerSource::Dhcpv4,
+            vec![dhcp_server.clone(), ndp_server.clone()],
+            None,
+        );
+
+        // No recorded health yet: every server is considered healthy.
+        assert_eq!(state.consolidate_by_health(), vec![dhcp_server.clone(), ndp_server.clone()]);
+
+        // One failure doesn't reach the threshold of 2.
+        state.record_query_result(&dhcp_server, false);
+        assert_eq!(state.consolidate_by_health(), vec![dhcp_server.clone(), ndp_server.clone()]);
+
+        // A second consecutive failure reaches the threshold: demoted to the tail.
+        state.record_query_result(&dhcp_server, false);
+        assert_eq!(state.consolidate_by_health(), vec![ndp_server.clone(), dhcp_server.clone()]);
+
+        // A success resets the streak and restores priority order.
+        state.record_query_result(&dhcp_server, true);
+        assert_eq!(state.consolidate_by_health(), vec![dhcp_server, ndp_server]);
+    }
+
+    /// A [`TimeSource`] that only advances when explicitly told to, so tests can deterministically
+    /// exercise TTL expiration without sleeping.
+    struct FakeTimeSource(Mutex<Instant>);
+
+    impl FakeTimeSource {
+        fn new(now: Instant) -> Arc<Self> {
+            Arc::new(Self(Mutex::new(now)))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock() += by;
+        }
+    }
+
+    impl TimeSource for Arc<FakeTimeSource> {
+        fn now(&self) -> Instant {
+            *self.0.lock()
+        }
+    }
+
+    #[test]
+    fn test_consolidate_expires_sources() {
+        let clock = FakeTimeSource::new(Instant::now());
+        let state = ServerConfigState::new_with_policy(
+            DEFAULT_FAILURE_THRESHOLD,
+            DEFAULT_PROBE_WINDOW,
+            clock.clone(),
+        );
+        let dhcp_server: DnsServer = DHCP_SERVER.into();
+
+        state.set_servers_for_source(
+            DnsServerSource::Dhcpv4,
+            vec![dhcp_server.clone()],
+            Some(clock.now() + Duration::from_secs(60)),
+        );
+
+        // Still valid: included in the consolidated list.
+        assert_eq!(state.consolidate(), vec![dhcp_server.clone()]);
+
+        // Past the deadline: the whole source is skipped, not just pruned.
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(state.consolidate(), vec![]);
+
+        // A source with no TTL never expires.
+        state.set_servers_for_source(DnsServerSource::Static, vec![dhcp_server.clone()], None);
+        clock.advance(Duration::from_secs(1_000_000));
+        assert_eq!(state.consolidate(), vec![dhcp_server]);
+    }
+
+    #[test]
+    fn test_expire_sources() {
+        let clock = FakeTimeSource::new(Instant::now());
+        let state = ServerConfigState::new_with_policy(
+            DEFAULT_FAILURE_THRESHOLD,
+            DEFAULT_PROBE_WINDOW,
+            clock.clone(),
+        );
+        let dhcp_server: DnsServer = DHCP_SERVER.into();
+
+        // No source has a TTL set, so there's nothing to expire and `nearest_expiry` is `None`.
+        assert_eq!(state.nearest_expiry(), None);
+
+        state.set_servers_for_source(
+            DnsServerSource::Dhcpv4,
+            vec![dhcp_server.clone()],
+            Some(clock.now() + Duration::from_secs(60)),
+        );
+        assert_eq!(state.nearest_expiry(), Some(clock.now() + Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(61));
+        state.expire_sources();
+        assert_eq!(state.consolidate(), vec![]);
+        assert_eq!(state.nearest_expiry(), None);
+    }
+
+    #[fasync::run_singlethreaded(test)]
+    async fn test_record_inspect() {
+        let inspector = fuchsia_inspect::Inspector::new();
+        let mut policy = ServerConfigSink::new(futures::sink::drain());
+        policy.set_inspect_node(inspector.root().create_child("dns_policy"));
+
+        let dhcp_server: DnsServer = DHCP_SERVER.into();
+        let ndp_server: DnsServer = NDP_SERVER.into();
+
+        policy
+            .set_servers_for_source(DnsServerSource::Dhcpv4, vec![dhcp_server.clone()])
+            .await
+            .expect("failed to set servers");
+
+        fuchsia_inspect::assert_inspect_tree!(inspector, root: {
+            dns_policy: {
+                Dhcpv4: {
+                    servers: format!("{:?}", dhcp_server),
+                },
+                consolidated: {
+                    servers: format!("{:?}", dhcp_server),
+                },
+                consolidation_count: 1u64,
+            }
+        });
+
+        // A second update from a different source replaces the old snapshot rather than
+        // accumulating alongside it, and bumps the counter.
+        policy
+            .set_servers_for_source(DnsServerSource::Ndp, vec![ndp_server.clone()])
+            .await
+            .expect("failed to set servers");
+
+        fuchsia_inspect::assert_inspect_tree!(inspector, root: {
+            dns_policy: {
+                Dhcpv4: {
+                    servers: format!("{:?}", dhcp_server),
+                },
+                Ndp: {
+                    servers: format!("{:?}", ndp_server),
+                },
+                consolidated: {
+                    servers: format!("{:?}\n{:?}", dhcp_server, ndp_server),
+                },
+                consolidation_count: 2u64,
+            }
+        });
+    }
 }
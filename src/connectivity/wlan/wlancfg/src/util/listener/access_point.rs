@@ -1,7 +1,10 @@
 use {
-    super::generic::{CurrentStateCache, Listener, Message},
+    super::generic::{CurrentStateCache, Listener},
     fidl_fuchsia_wlan_policy as fidl_policy,
-    futures::{channel::mpsc, future::BoxFuture, prelude::*},
+    fuchsia_async as fasync,
+    futures::{channel::mpsc, future::BoxFuture, lock::Mutex, prelude::*},
+    std::collections::{HashMap, HashSet},
+    std::sync::Arc,
 };
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -54,6 +57,60 @@ impl CurrentStateCache for ApStatesUpdate {
     }
 }
 
+/// A stable identifier for an `ApStateUpdate` within a single `ApStatesUpdate`, used to match
+/// entries across two snapshots when diffing. Band+frequency is the closest thing to a stable AP
+/// identity this FIDL type carries.
+type ApKey = (Option<fidl_policy::OperatingBand>, Option<u32>);
+
+fn ap_key(ap: &ApStateUpdate) -> ApKey {
+    (ap.band, ap.frequency)
+}
+
+/// The result of diffing one `ApStatesUpdate` against another: which access points are newly
+/// present, which changed in place, and which disappeared.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ApStatesDelta {
+    pub added: Vec<ApStateUpdate>,
+    pub changed: Vec<ApStateUpdate>,
+    pub removed: Vec<ApKey>,
+}
+
+impl ApStatesDelta {
+    /// The access points an already-synced subscriber needs to hear about: anything newly seen
+    /// or whose fields changed. `removed` isn't relayed through this path since the
+    /// `fuchsia.wlan.policy` hanging-get update carries AP state, not a removal notice.
+    fn changed_access_points(&self) -> Vec<ApStateUpdate> {
+        self.added.iter().chain(self.changed.iter()).cloned().collect()
+    }
+}
+
+impl ApStatesUpdate {
+    /// Computes the delta from `self` (a previously observed snapshot) to `new_state`, keyed by
+    /// band+frequency so a subscriber only needs to look at entries that actually changed.
+    fn diff(&self, new_state: &ApStatesUpdate) -> ApStatesDelta {
+        let previous: HashMap<ApKey, &ApStateUpdate> =
+            self.access_points.iter().map(|ap| (ap_key(ap), ap)).collect();
+        let mut seen = HashSet::new();
+        let mut delta = ApStatesDelta::default();
+
+        for ap in &new_state.access_points {
+            let key = ap_key(ap);
+            seen.insert(key);
+            match previous.get(&key) {
+                None => delta.added.push(ap.clone()),
+                Some(prev) if *prev != ap => delta.changed.push(ap.clone()),
+                Some(_) => {}
+            }
+        }
+        for (key, _) in previous {
+            if !seen.contains(&key) {
+                delta.removed.push(key);
+            }
+        }
+        delta
+    }
+}
+
 impl Listener<Vec<fidl_policy::AccessPointState>> for fidl_policy::AccessPointStateUpdatesProxy {
     fn notify_listener(
         self,
@@ -68,9 +125,136 @@ impl Listener<Vec<fidl_policy::AccessPointState>> for fidl_policy::AccessPointSt
     }
 }
 
-// Helpful aliases for servicing client updates
-pub type ApMessage = Message<fidl_policy::AccessPointStateUpdatesProxy, ApStatesUpdate>;
-pub type ApMessageSender = mpsc::UnboundedSender<ApMessage>;
+/// A shared fan-out queue for AP state updates, modeled on the omaha-client `event_queue`
+/// pattern: any number of `AccessPointStateUpdatesProxy` subscribers can register concurrently,
+/// each is handed the current cached state immediately upon registering, and thereafter every
+/// merged update is pushed out to all of them independently.
+///
+/// This is the only entry point the AP listener loop should use for both registering new
+/// `AccessPointStateUpdatesProxy` subscribers (`add_subscriber`, driven by
+/// `serve_access_point_listeners` below) and publishing state changes (`notify_subscribers`); it
+/// supersedes a prior single-subscriber unbounded-mpsc design, which could only ever serve one
+/// listener at a time.
+///
+/// Each subscriber is serviced by its own task that reads the shared cache on demand rather than
+/// being handed an update directly: `notify_subscribers` only sends it a wakeup, and the task
+/// folds every wakeup already queued into a single read of the cache, so any number of
+/// `notify_subscribers` calls between two deliveries collapse into one delivery of the latest
+/// state. A subscriber whose `notify_listener` call reports the FIDL channel closed
+/// (`None`) is dropped from the queue.
+///
+/// Once a subscriber has received its first snapshot, later deliveries only carry the access
+/// points that are new or changed since the last one sent to *that* subscriber, keyed by
+/// band+frequency (see `ApStatesUpdate::diff`). This avoids re-sending every AP's full state over
+/// FIDL just because one of them had a client count change.
+pub struct ApEventQueue {
+    cache: Arc<Mutex<ApStatesUpdate>>,
+    subscribers: Vec<Subscriber>,
+}
+
+struct Subscriber {
+    last_sent: Arc<Mutex<Option<ApStatesUpdate>>>,
+    wake: mpsc::Sender<()>,
+}
+
+impl ApEventQueue {
+    pub fn new() -> Self {
+        Self {
+            cache: Arc::new(Mutex::new(<ApStatesUpdate as CurrentStateCache>::default())),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Registers `proxy` as a new subscriber, immediately queuing it the current cached state.
+    pub fn add_subscriber(&mut self, proxy: fidl_policy::AccessPointStateUpdatesProxy) {
+        let last_sent = Arc::new(Mutex::new(None));
+        let (wake_tx, wake_rx) = mpsc::channel::<()>(1);
+        self.subscribers.push(Subscriber { last_sent: last_sent.clone(), wake: wake_tx.clone() });
+        spawn_subscriber_task(proxy, self.cache.clone(), last_sent, wake_rx);
+        // Kick the task so it picks up the initial state queued above.
+        let _ = wake_tx.clone().try_send(());
+    }
+
+    /// Merges `update` into the cache and wakes every live subscriber so it picks up the result,
+    /// pruning any whose channel has since closed.
+    pub async fn notify_subscribers(&mut self, update: ApStatesUpdate) {
+        self.cache.lock().await.merge_in_update(update);
+
+        let mut live_subscribers = Vec::with_capacity(self.subscribers.len());
+        for subscriber in self.subscribers.drain(..) {
+            if subscriber.wake.is_closed() {
+                continue;
+            }
+            // A full wake channel just means the subscriber is already scheduled to pick up the
+            // latest state on its next iteration; either way it keeps the subscriber.
+            let _ = subscriber.wake.clone().try_send(());
+            live_subscribers.push(subscriber);
+        }
+        self.subscribers = live_subscribers;
+    }
+}
+
+/// Serves an `AccessPointListener` protocol request stream, routing every `GetListener` request's
+/// proxy into `queue` via `add_subscriber`. This is the AP listener loop's entry point into
+/// `ApEventQueue`: it replaces the prior per-connection `ApMessageSender` hookup, but keeps the
+/// same calling convention the FIDL server dispatch used it with, just wired to the shared queue
+/// instead of a single-subscriber channel. Each accepted request is handed off immediately so one
+/// slow or malformed request can't stall the ones after it; the stream itself is drained until the
+/// channel closes or yields an error.
+pub async fn serve_access_point_listeners(
+    queue: Arc<Mutex<ApEventQueue>>,
+    mut requests: fidl_policy::AccessPointListenerRequestStream,
+) {
+    while let Some(request) = requests.next().await {
+        let fidl_policy::AccessPointListenerRequest::GetListener { updates, .. } = match request {
+            Ok(request) => request,
+            Err(_) => break,
+        };
+        if let Ok(proxy) = updates.into_proxy() {
+            queue.lock().await.add_subscriber(proxy);
+        }
+    }
+}
+
+/// Drives a single subscriber: each time it's woken, read the shared cache and deliver either the
+/// full snapshot (if this subscriber has never been sent one) or just the access points that
+/// changed since the last delivery, exiting once `notify_listener` reports the channel closed.
+fn spawn_subscriber_task(
+    proxy: fidl_policy::AccessPointStateUpdatesProxy,
+    cache: Arc<Mutex<ApStatesUpdate>>,
+    last_sent: Arc<Mutex<Option<ApStatesUpdate>>>,
+    mut wake: mpsc::Receiver<()>,
+) {
+    fasync::spawn_local(async move {
+        let mut proxy = Some(proxy);
+        while wake.next().await.is_some() {
+            // Fold any further wakeups already queued into this one delivery.
+            while let Ok(Some(())) = wake.try_next() {}
+            let current = cache.lock().await.clone();
+            let fidl_update: Vec<fidl_policy::AccessPointState> = {
+                let mut last_sent = last_sent.lock().await;
+                let fidl_update = match &*last_sent {
+                    None => current.clone().into(),
+                    Some(prev) => {
+                        let delta = prev.diff(&current);
+                        ApStatesUpdate { access_points: delta.changed_access_points() }.into()
+                    }
+                };
+                *last_sent = Some(current);
+                fidl_update
+            };
+
+            let p = match proxy.take() {
+                Some(p) => p,
+                None => break,
+            };
+            proxy = p.notify_listener(fidl_update).await.map(|boxed| *boxed);
+            if proxy.is_none() {
+                break;
+            }
+        }
+    });
+}
 
 #[cfg(test)]
 mod tests {
@@ -114,6 +298,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn diff_access_points() {
+        let original = ApStatesUpdate {
+            access_points: vec![
+                ApStateUpdate {
+                    state: fidl_policy::OperatingState::Active,
+                    mode: Some(fidl_policy::ConnectivityMode::Unrestricted),
+                    band: Some(fidl_policy::OperatingBand::Any),
+                    frequency: Some(2437),
+                    clients: Some(ConnectedClientInformation { count: 1 }),
+                },
+                ApStateUpdate {
+                    state: fidl_policy::OperatingState::Active,
+                    mode: Some(fidl_policy::ConnectivityMode::Unrestricted),
+                    band: Some(fidl_policy::OperatingBand::Any),
+                    frequency: Some(5180),
+                    clients: Some(ConnectedClientInformation { count: 2 }),
+                },
+            ],
+        };
+
+        // One AP's client count changes, one disappears, and a new one shows up.
+        let updated = ApStatesUpdate {
+            access_points: vec![
+                ApStateUpdate {
+                    state: fidl_policy::OperatingState::Active,
+                    mode: Some(fidl_policy::ConnectivityMode::Unrestricted),
+                    band: Some(fidl_policy::OperatingBand::Any),
+                    frequency: Some(2437),
+                    clients: Some(ConnectedClientInformation { count: 3 }),
+                },
+                ApStateUpdate {
+                    state: fidl_policy::OperatingState::Starting,
+                    mode: Some(fidl_policy::ConnectivityMode::Unrestricted),
+                    band: Some(fidl_policy::OperatingBand::Any),
+                    frequency: Some(2462),
+                    clients: Some(ConnectedClientInformation { count: 0 }),
+                },
+            ],
+        };
+
+        let delta = original.diff(&updated);
+        assert_eq!(delta.added, vec![updated.access_points[1].clone()]);
+        assert_eq!(delta.changed, vec![updated.access_points[0].clone()]);
+        assert_eq!(delta.removed, vec![(Some(fidl_policy::OperatingBand::Any), Some(5180))]);
+
+        let changed_access_points = delta.changed_access_points();
+        assert_eq!(changed_access_points.len(), 2);
+        assert!(changed_access_points.contains(&updated.access_points[0]));
+        assert!(changed_access_points.contains(&updated.access_points[1]));
+    }
+
     #[test]
     fn into_fidl() {
         let state = ApStatesUpdate {
@@ -9,10 +9,59 @@ use {
     },
     argh::FromArgs,
     async_trait::async_trait,
+    fuchsia_async::{Time, Timer},
     fuchsia_inspect_node_hierarchy::NodeHierarchy,
+    fuchsia_zircon as zx,
+    serde::Serialize,
+    serde_json,
     selectors,
+    std::collections::HashSet,
+    std::str::FromStr,
 };
 
+/// How long to wait between polls while `--subscribe` is watching for components to
+/// appear/disappear. See [`SelectorsCommand::subscribe_loop`] for why this polls rather than
+/// subscribing to an event-driven stream.
+const SUBSCRIBE_POLL_INTERVAL: zx::Duration = zx::Duration::from_seconds(1);
+
+/// The output format for `selectors`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SelectorsFormat {
+    /// One full selector string per line (the default).
+    Text,
+    /// One JSON record per line: `{ component, node_path, property, property_type }`.
+    Json,
+}
+
+impl Default for SelectorsFormat {
+    fn default() -> Self {
+        SelectorsFormat::Text
+    }
+}
+
+impl FromStr for SelectorsFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(SelectorsFormat::Text),
+            "json" => Ok(SelectorsFormat::Json),
+            _ => Err(format!("Invalid format \"{}\". Expected one of: text, json", value)),
+        }
+    }
+}
+
+/// A structured record for a single selector, used when `--format json` is requested.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SelectorEntry {
+    pub component: String,
+    pub node_path: String,
+    pub property: String,
+    pub property_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
 /// Lists all available full selectors (component selector + tree selector).
 /// If a selector is provided, it’ll only print selectors for that component.
 /// If a full selector (component + tree) is provided, it lists all selectors under the given node.
@@ -24,6 +73,31 @@ pub struct SelectorsCommand {
     /// will only contain monikers for components whose url contains the provided name.
     pub manifest: Option<String>,
 
+    #[argh(option, default = "SelectorsFormat::Text")]
+    /// the output format to use. One of `text` (the default; a flat selector string per line) or
+    /// `json` (a structured `{ component, node_path, property, property_type }` record per line).
+    pub format: SelectorsFormat,
+
+    #[argh(switch)]
+    /// instead of a single snapshot, keep running and poll for matching components starting and
+    /// stopping: an initial batch is emitted for currently-matching components, followed by
+    /// incremental batches (`- `-prefixed for components that disappeared) as the match set
+    /// changes.
+    pub subscribe: bool,
+
+    #[argh(option)]
+    /// restrict output to properties whose type is in this comma-separated list. Valid types:
+    /// int, uint, double, string, bytes, bool, int_array, uint_array, double_array.
+    pub r#type: Option<String>,
+
+    #[argh(option)]
+    /// only include properties whose (sanitized) name contains this substring.
+    pub contains: Option<String>,
+
+    #[argh(switch)]
+    /// append the property's current value to each selector, as an extra annotation column.
+    pub show_values: bool,
+
     #[argh(positional)]
     /// selectors for which the selectors should be queried. Minimum: 1 unless `--manifest` is set.
     /// When `--manifest` is provided then the selectors should be tree selectors, otherwise
@@ -40,19 +114,102 @@ impl Command for SelectorsCommand {
             return Err(Error::invalid_arguments("Expected 1 or more selectors. Got zero."));
         }
         let selectors = utils::get_selectors_for_manifest(&self.manifest, &self.selectors).await?;
-        let mut result = utils::fetch_data(&selectors)
+
+        if self.subscribe {
+            self.subscribe_loop(&selectors).await?;
+            return Ok(Vec::new());
+        }
+
+        let mut result = self.fetch_formatted(&selectors).await?;
+        result.sort();
+        Ok(result)
+    }
+}
+
+impl SelectorsCommand {
+    /// Fetches a single snapshot of matching selectors, filtered by `--type`/`--contains` and
+    /// rendered per `self.format`.
+    async fn fetch_formatted(&self, selectors: &[String]) -> Result<Vec<String>, Error> {
+        let allowed_types = self.allowed_types();
+        Ok(utils::fetch_data(selectors)
             .await?
             .into_iter()
             .flat_map(|(component_selector, hierarchy)| {
-                get_selectors(component_selector, hierarchy)
+                get_selector_entries(component_selector, hierarchy)
             })
-            .collect::<Vec<_>>();
-        result.sort();
-        Ok(result)
+            .filter(|entry| {
+                allowed_types
+                    .as_ref()
+                    .map_or(true, |types| types.contains(entry.property_type.as_str()))
+            })
+            .filter(|entry| {
+                self.contains.as_ref().map_or(true, |needle| entry.property.contains(needle))
+            })
+            .map(|mut entry| {
+                if !self.show_values {
+                    entry.value = None;
+                }
+                render_entry(self.format, entry)
+            })
+            .collect::<Vec<_>>())
+    }
+
+    /// Parses `--type int,uint,...` into a set of allowed property type names, if provided.
+    fn allowed_types(&self) -> Option<HashSet<String>> {
+        self.r#type
+            .as_ref()
+            .map(|types| types.split(',').map(|t| t.trim().to_string()).collect())
+    }
+
+    /// A polling fallback for `--subscribe`: prints an initial batch for every currently-matching
+    /// component, then re-fetches every `SUBSCRIBE_POLL_INTERVAL` and diffs against the previous
+    /// batch, printing newly observed selectors and `- `-prefixing ones that disappeared.
+    ///
+    /// `utils::fetch_data` has no event-driven watch API, only a point-in-time snapshot, so a
+    /// true snapshot-then-subscribe stream (no periodic re-fetch) isn't available yet; this
+    /// poll-and-diff loop is the closest approximation.
+    async fn subscribe_loop(&self, selectors: &[String]) -> Result<(), Error> {
+        let mut seen: HashSet<String> = HashSet::new();
+
+        loop {
+            let mut batch = self.fetch_formatted(selectors).await?;
+            batch.sort();
+            let current: HashSet<String> = batch.into_iter().collect();
+
+            let mut removed: Vec<_> = seen.difference(&current).cloned().collect();
+            removed.sort();
+            for selector in removed.drain(..) {
+                println!("- {}", selector);
+            }
+
+            let mut added: Vec<_> = current.difference(&seen).cloned().collect();
+            added.sort();
+            for selector in added.drain(..) {
+                println!("{}", selector);
+            }
+
+            seen = current;
+            Timer::new(Time::after(SUBSCRIBE_POLL_INTERVAL)).await;
+        }
+    }
+}
+
+fn render_entry(format: SelectorsFormat, entry: SelectorEntry) -> String {
+    match format {
+        SelectorsFormat::Text => {
+            let selector = format!("{}:{}:{}", entry.component, entry.node_path, entry.property);
+            match &entry.value {
+                Some(value) => format!("{}\t{}={}", selector, entry.property_type, value),
+                None => selector,
+            }
+        }
+        SelectorsFormat::Json => {
+            serde_json::to_string(&entry).unwrap_or_else(|_| entry.property.clone())
+        }
     }
 }
 
-fn get_selectors(component_selector: String, hierarchy: NodeHierarchy) -> Vec<String> {
+fn get_selector_entries(component_selector: String, hierarchy: NodeHierarchy) -> Vec<SelectorEntry> {
     hierarchy
         .property_iter()
         .flat_map(|(node_path, maybe_property)| maybe_property.map(|prop| (node_path, prop)))
@@ -63,7 +220,45 @@ fn get_selectors(component_selector: String, hierarchy: NodeHierarchy) -> Vec<St
                 .collect::<Vec<String>>()
                 .join("/");
             let property_selector = selectors::sanitize_string_for_selectors(property.name());
-            format!("{}:{}:{}", component_selector, node_selector, property_selector)
+            SelectorEntry {
+                component: component_selector.clone(),
+                node_path: node_selector,
+                property: property_selector,
+                property_type: property_type_name(&property).to_string(),
+                value: Some(property_value_string(&property)),
+            }
         })
         .collect()
 }
+
+/// Renders a property's current value as a short human-readable string, for the
+/// `--show-values` annotation column.
+fn property_value_string(property: &fuchsia_inspect_node_hierarchy::Property) -> String {
+    use fuchsia_inspect_node_hierarchy::Property::*;
+    match property {
+        String(_, v) => v.clone(),
+        Bytes(_, v) => format!("[{} bytes]", v.len()),
+        Int(_, v) => v.to_string(),
+        Uint(_, v) => v.to_string(),
+        Double(_, v) => v.to_string(),
+        Bool(_, v) => v.to_string(),
+        DoubleArray(_, v) => format!("{:?}", v),
+        IntArray(_, v) => format!("{:?}", v),
+        UintArray(_, v) => format!("{:?}", v),
+    }
+}
+
+fn property_type_name(property: &fuchsia_inspect_node_hierarchy::Property) -> &'static str {
+    use fuchsia_inspect_node_hierarchy::Property::*;
+    match property {
+        String(..) => "string",
+        Bytes(..) => "bytes",
+        Int(..) => "int",
+        Uint(..) => "uint",
+        Double(..) => "double",
+        Bool(..) => "bool",
+        DoubleArray(..) => "double_array",
+        IntArray(..) => "int_array",
+        UintArray(..) => "uint_array",
+    }
+}
@@ -15,25 +15,91 @@ use {
         rights::{Rights, READ_RIGHTS, WRITE_RIGHTS},
     },
     async_trait::async_trait,
-    cm_rust::{CapabilityPath, ExposeDecl, ExposeDirectoryDecl, ExposeProtocolDecl},
+    cm_rust::{
+        CapabilityPath, ExposeDecl, ExposeDirectoryDecl, ExposeProtocolDecl, ExposeResolverDecl,
+        ExposeRunnerDecl, ExposeServiceDecl,
+    },
     fidl::endpoints::{Proxy, ServerEnd},
     fidl_fuchsia_io::{self as fio, DirectoryProxy, NodeEvent, NodeMarker, NodeProxy},
-    fuchsia_async as fasync, fuchsia_zircon as zx,
+    files_async,
+    fuchsia_async::{self as fasync, TimeoutExt},
+    fuchsia_zircon as zx,
     futures::stream::StreamExt,
     io_util,
     log::*,
+    std::collections::HashSet,
     std::sync::{Arc, Weak},
 };
 
+/// The amount of time to wait after the first change notification on a watched directory before
+/// dispatching the coalesced set of `CapabilityReady`/`CapabilityRemoved` events. This prevents a
+/// directory that churns many entries in a short window from flooding the hook pipeline with one
+/// event per entry.
+const DIRECTORY_WATCH_COALESCE_DELAY: zx::Duration = zx::Duration::from_millis(100);
+
+/// Default amount of time to wait for a component's outgoing directory (or an exposed
+/// capability's `OnOpen`) before giving up and dispatching an error event.
+const DEFAULT_ON_OPEN_TIMEOUT: zx::Duration = zx::Duration::from_seconds(5);
+
+/// Default number of attempts made to open the outgoing directory during event synthesis before
+/// giving up.
+const DEFAULT_SYNTHESIS_RETRY_ATTEMPTS: u64 = 3;
+
+/// Default delay between synthesis retry attempts.
+const DEFAULT_SYNTHESIS_RETRY_DELAY: zx::Duration = zx::Duration::from_millis(300);
+
 /// Awaits for `Started` events and for each capability exposed to framework, dispatches a
 /// `CapabilityReady` event.
 pub struct CapabilityReadyNotifier {
     model: Weak<Model>,
+    /// Whether, in addition to the initial `CapabilityReady` event, directory capabilities
+    /// should be watched via `fuchsia.io/Directory.Watch` for incremental updates as entries are
+    /// added or removed at runtime.
+    watch_for_directory_changes: bool,
+    /// How long to wait for an `OnOpen` event before giving up on a capability.
+    on_open_timeout: zx::Duration,
+    /// How many times to retry opening the outgoing directory during event synthesis.
+    synthesis_retry_attempts: u64,
+    /// Delay between synthesis retry attempts.
+    synthesis_retry_delay: zx::Duration,
 }
 
 impl CapabilityReadyNotifier {
     pub fn new(model: Weak<Model>) -> Self {
-        Self { model }
+        Self::new_with_options(model, false)
+    }
+
+    /// Like `new`, but additionally opts into watching exposed directory capabilities for
+    /// incremental changes (see `watch_for_directory_changes` on the returned notifier).
+    pub fn new_watching_directories(model: Weak<Model>) -> Self {
+        Self::new_with_options(model, true)
+    }
+
+    fn new_with_options(model: Weak<Model>, watch_for_directory_changes: bool) -> Self {
+        Self {
+            model,
+            watch_for_directory_changes,
+            on_open_timeout: DEFAULT_ON_OPEN_TIMEOUT,
+            synthesis_retry_attempts: DEFAULT_SYNTHESIS_RETRY_ATTEMPTS,
+            synthesis_retry_delay: DEFAULT_SYNTHESIS_RETRY_DELAY,
+        }
+    }
+
+    /// Like `new`, but allows tests to shrink the `OnOpen` timeout and the event-synthesis
+    /// retry/backoff so they don't have to wait out the production-sized defaults.
+    pub fn new_for_test(
+        model: Weak<Model>,
+        on_open_timeout: zx::Duration,
+        synthesis_retry_attempts: u64,
+        synthesis_retry_delay: zx::Duration,
+    ) -> Self {
+        Self {
+            model,
+            watch_for_directory_changes: false,
+            on_open_timeout,
+            synthesis_retry_attempts,
+            synthesis_retry_delay,
+        }
     }
 
     pub fn hooks(self: &Arc<Self>) -> Vec<HooksRegistration> {
@@ -78,8 +144,9 @@ impl CapabilityReadyNotifier {
         Ok(())
     }
 
-    /// Waits for the OnOpen event on the directory. This will hang until the component starts
-    /// serving that directory. The directory should have been cloned/opened with DESCRIBE.
+    /// Waits for the OnOpen event on the directory, up to `self.on_open_timeout`. If the
+    /// component never starts serving the directory within that window this returns an error
+    /// instead of hanging forever. The directory should have been cloned/opened with DESCRIBE.
     async fn wait_for_on_open(
         &self,
         node: &NodeProxy,
@@ -87,7 +154,10 @@ impl CapabilityReadyNotifier {
         path: String,
     ) -> Result<(), ModelError> {
         let mut events = node.take_event_stream();
-        match events.next().await {
+        let next_event = events
+            .next()
+            .on_timeout(fasync::Time::after(self.on_open_timeout), || None);
+        match next_event.await {
             Some(Ok(NodeEvent::OnOpen_ { s: status, info: _ })) => zx::Status::ok(status)
                 .map_err(|_| ModelError::open_directory_error(target_moniker.clone(), path)),
             _ => Err(ModelError::open_directory_error(target_moniker.clone(), path)),
@@ -129,44 +199,152 @@ impl CapabilityReadyNotifier {
         }
         .await;
 
+        self.create_events_from_directory(outgoing_dir_result, expose_decls, target_realm).await
+    }
+
+    /// Like `create_events`, but for callers (namely event synthesis) that have already resolved
+    /// the outgoing directory -- possibly after retrying -- and so don't need `create_events` to
+    /// redo the `OnOpen` wait.
+    async fn create_events_from_directory(
+        &self,
+        outgoing_dir_result: Result<DirectoryProxy, ModelError>,
+        expose_decls: Vec<ExposeDecl>,
+        target_realm: &Arc<Realm>,
+    ) -> Vec<Event> {
         let mut events = Vec::new();
         for expose_decl in expose_decls {
-            let event = match expose_decl {
+            match expose_decl {
                 ExposeDecl::Directory(ExposeDirectoryDecl {
                     source_path,
                     target_path,
                     rights,
                     ..
                 }) => {
-                    self.create_event(
-                        &target_realm,
-                        outgoing_dir_result.as_ref(),
-                        fio::MODE_TYPE_DIRECTORY,
-                        Rights::from(rights.unwrap_or(*READ_RIGHTS)),
-                        source_path,
-                        target_path,
-                    )
-                    .await
+                    events.push(
+                        self.create_event(
+                            &target_realm,
+                            outgoing_dir_result.as_ref(),
+                            fio::MODE_TYPE_DIRECTORY,
+                            Rights::from(rights.unwrap_or(*READ_RIGHTS)),
+                            source_path,
+                            target_path,
+                        )
+                        .await,
+                    );
                 }
                 ExposeDecl::Protocol(ExposeProtocolDecl { source_path, target_path, .. }) => {
-                    self.create_event(
-                        &target_realm,
-                        outgoing_dir_result.as_ref(),
-                        fio::MODE_TYPE_SERVICE,
-                        Rights::from(*WRITE_RIGHTS),
-                        source_path,
-                        target_path,
-                    )
-                    .await
+                    events.push(
+                        self.create_event(
+                            &target_realm,
+                            outgoing_dir_result.as_ref(),
+                            fio::MODE_TYPE_SERVICE,
+                            Rights::from(*WRITE_RIGHTS),
+                            source_path,
+                            target_path,
+                        )
+                        .await,
+                    );
+                }
+                ExposeDecl::Service(ExposeServiceDecl { source_path, target_path, .. }) => {
+                    events.extend(
+                        self.create_service_instance_events(
+                            &target_realm,
+                            outgoing_dir_result.as_ref(),
+                            source_path,
+                            target_path,
+                        )
+                        .await,
+                    );
+                }
+                ExposeDecl::Runner(ExposeRunnerDecl { source_path, target_path, .. })
+                | ExposeDecl::Resolver(ExposeResolverDecl { source_path, target_path, .. }) => {
+                    events.push(
+                        self.create_event(
+                            &target_realm,
+                            outgoing_dir_result.as_ref(),
+                            fio::MODE_TYPE_SERVICE,
+                            Rights::from(*WRITE_RIGHTS),
+                            source_path,
+                            target_path,
+                        )
+                        .await,
+                    );
                 }
-                _ => continue,
             };
-            events.push(event);
         }
 
         events
     }
 
+    /// A service capability is served as a directory of per-instance subdirectories. Open that
+    /// directory and emit one `CapabilityReady` node per instance found inside it, so framework
+    /// consumers see each aggregated service instance individually rather than nothing at all.
+    async fn create_service_instance_events(
+        &self,
+        target_realm: &Arc<Realm>,
+        outgoing_dir_result: Result<&DirectoryProxy, &ModelError>,
+        source_path: CapabilityPath,
+        target_path: CapabilityPath,
+    ) -> Vec<Event> {
+        let instances = async {
+            let outgoing_dir = outgoing_dir_result.map_err(|e| e.clone())?;
+            let canonicalized_path = io_util::canonicalize_path(&source_path.to_string());
+            let service_dir = io_util::open_directory(
+                outgoing_dir,
+                &std::path::PathBuf::from(canonicalized_path),
+                fio::OPEN_RIGHT_READABLE,
+            )
+            .map_err(|_| {
+                ModelError::open_directory_error(
+                    target_realm.abs_moniker.clone(),
+                    source_path.to_string(),
+                )
+            })?;
+            files_async::readdir(&service_dir).await.map_err(|_| {
+                ModelError::open_directory_error(
+                    target_realm.abs_moniker.clone(),
+                    source_path.to_string(),
+                )
+            })
+        }
+        .await;
+
+        let instances = match instances {
+            Ok(instances) => instances,
+            Err(e) => {
+                return vec![Event::new(
+                    target_realm,
+                    Err(EventError::new(
+                        &e,
+                        EventErrorPayload::CapabilityReady { path: target_path.to_string() },
+                    )),
+                )]
+            }
+        };
+
+        let mut events = Vec::with_capacity(instances.len());
+        for instance in instances {
+            let instance_target_path = CapabilityPath {
+                dirname: target_path.to_string(),
+                basename: instance.name.clone(),
+            };
+            let instance_source_path =
+                CapabilityPath { dirname: source_path.to_string(), basename: instance.name };
+            events.push(
+                self.create_event(
+                    target_realm,
+                    outgoing_dir_result,
+                    fio::MODE_TYPE_SERVICE,
+                    Rights::from(*WRITE_RIGHTS),
+                    instance_source_path,
+                    instance_target_path,
+                )
+                .await,
+            );
+        }
+        events
+    }
+
     /// Creates an event with the directory at the given `target_path` inside the provided
     /// outgoing directory if the capability is available.
     async fn create_event(
@@ -207,6 +385,17 @@ impl CapabilityReadyNotifier {
         }
         .await;
 
+        if self.watch_for_directory_changes && mode == fio::MODE_TYPE_DIRECTORY {
+            if let Ok(outgoing_dir) = outgoing_dir_result {
+                self.spawn_directory_watcher(
+                    target_realm,
+                    outgoing_dir,
+                    source_path,
+                    target_path.clone(),
+                );
+            }
+        }
+
         match node_result {
             Ok(node) => Event::new(
                 &target_realm,
@@ -218,6 +407,212 @@ impl CapabilityReadyNotifier {
             ),
         }
     }
+
+    /// Spawns a task that watches the directory at `source_path` (relative to `outgoing_dir`)
+    /// for added/removed entries and dispatches incremental `CapabilityReady`/`CapabilityRemoved`
+    /// events through `target_realm.hooks` as they occur. The task exits once the realm's model
+    /// is dropped or the watched directory is closed.
+    fn spawn_directory_watcher(
+        &self,
+        target_realm: &Arc<Realm>,
+        outgoing_dir: &DirectoryProxy,
+        source_path: CapabilityPath,
+        target_path: String,
+    ) {
+        let model = self.model.clone();
+        let target_realm = target_realm.clone();
+        let canonicalized_path = io_util::canonicalize_path(&source_path.to_string()).to_string();
+
+        let (watch_node, watch_server_end) =
+            match fidl::endpoints::create_proxy::<NodeMarker>() {
+                Ok(v) => v,
+                Err(_) => return,
+            };
+        if outgoing_dir
+            .open(
+                fio::OPEN_RIGHT_READABLE,
+                fio::MODE_TYPE_DIRECTORY,
+                &canonicalized_path,
+                ServerEnd::new(watch_server_end.into_channel()),
+            )
+            .is_err()
+        {
+            return;
+        }
+        let watch_dir = match io_util::node_to_directory(watch_node) {
+            Ok(d) => d,
+            Err(_) => return,
+        };
+
+        fasync::spawn(async move {
+            watch_directory_for_changes(model, target_realm, target_path, &watch_dir).await;
+        });
+    }
+}
+
+/// Decodes `fuchsia.io/Directory.Watch` events off `dir` and dispatches coalesced
+/// `CapabilityReady`/`CapabilityRemoved` events for entries added/removed after the initial
+/// snapshot (`WATCH_EVENT_EXISTING`/`WATCH_EVENT_IDLE`). Exits once `model` can no longer be
+/// upgraded (the realm/model was destroyed) or the watch channel closes.
+async fn watch_directory_for_changes(
+    model: Weak<Model>,
+    target_realm: Arc<Realm>,
+    target_path: String,
+    dir: &DirectoryProxy,
+) {
+    let (client_end, server_end) = match zx::Channel::create() {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+    if dir.watch(fio::WATCH_MASK_ALL, 0, server_end).await.is_err() {
+        return;
+    }
+    let watch_chan = match fasync::Channel::from_channel(client_end) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+
+    let mut pending_added: HashSet<String> = HashSet::new();
+    let mut pending_removed: HashSet<String> = HashSet::new();
+    let mut past_initial_idle = false;
+    // When the pending changes are due to be flushed; set by the first change after a flush.
+    let mut flush_deadline: Option<fasync::Time> = None;
+
+    loop {
+        if model.upgrade().is_none() {
+            return;
+        }
+
+        let mut buf = zx::MessageBuf::new();
+        // Wait for either the next batch of watch messages, or (once we have something
+        // pending) the coalescing deadline, whichever comes first.
+        let recv_result = if let Some(deadline) = flush_deadline {
+            match futures::future::select(
+                Box::pin(watch_chan.recv_msg(&mut buf)),
+                Box::pin(fasync::Timer::new(deadline)),
+            )
+            .await
+            {
+                futures::future::Either::Left((result, _)) => result,
+                futures::future::Either::Right((_, _)) => {
+                    flush_directory_changes(
+                        &target_realm,
+                        dir,
+                        &target_path,
+                        &mut pending_added,
+                        &mut pending_removed,
+                    )
+                    .await;
+                    flush_deadline = None;
+                    continue;
+                }
+            }
+        } else {
+            watch_chan.recv_msg(&mut buf).await
+        };
+
+        if recv_result.is_err() {
+            flush_directory_changes(
+                &target_realm,
+                dir,
+                &target_path,
+                &mut pending_added,
+                &mut pending_removed,
+            )
+            .await;
+            return;
+        }
+
+        let bytes = buf.bytes();
+        let mut offset = 0;
+        while offset + 2 <= bytes.len() {
+            let event = bytes[offset];
+            let len = bytes[offset + 1] as usize;
+            offset += 2;
+            if offset + len > bytes.len() {
+                break;
+            }
+            let name = String::from_utf8_lossy(&bytes[offset..offset + len]).into_owned();
+            offset += len;
+
+            match event {
+                fio::WATCH_EVENT_EXISTING => {}
+                fio::WATCH_EVENT_IDLE => past_initial_idle = true,
+                fio::WATCH_EVENT_ADDED if past_initial_idle => {
+                    pending_removed.remove(&name);
+                    pending_added.insert(name);
+                }
+                fio::WATCH_EVENT_REMOVED if past_initial_idle => {
+                    pending_added.remove(&name);
+                    pending_removed.insert(name);
+                }
+                _ => {}
+            }
+        }
+        if flush_deadline.is_none() && !(pending_added.is_empty() && pending_removed.is_empty()) {
+            flush_deadline = Some(fasync::Time::after(DIRECTORY_WATCH_COALESCE_DELAY));
+        }
+    }
+}
+
+/// Dispatches one coalesced round of `CapabilityReady`/`CapabilityRemoved` events for the
+/// entries accumulated in `pending_added`/`pending_removed`, then clears them. Added entries are
+/// (re-)opened off `dir` so the dispatched event carries a live node, matching the initial
+/// `CapabilityReady` event's shape.
+async fn flush_directory_changes(
+    target_realm: &Arc<Realm>,
+    dir: &DirectoryProxy,
+    target_path: &str,
+    pending_added: &mut HashSet<String>,
+    pending_removed: &mut HashSet<String>,
+) {
+    for name in pending_added.drain() {
+        let path = format!("{}/{}", target_path, name);
+        let event = match open_watched_entry(dir, &name).await {
+            Ok(node) => Event::new(target_realm, Ok(EventPayload::CapabilityReady { path, node })),
+            Err(e) => Event::new(
+                target_realm,
+                Err(EventError::new(&e, EventErrorPayload::CapabilityReady { path })),
+            ),
+        };
+        target_realm.hooks.dispatch(&event).await.unwrap_or_else(|e| {
+            error!(
+                "Error notifying incremental capability ready for {}: {:?}",
+                target_realm.abs_moniker, e
+            )
+        });
+    }
+    for name in pending_removed.drain() {
+        let path = format!("{}/{}", target_path, name);
+        let event = Event::new(target_realm, Ok(EventPayload::CapabilityRemoved { path }));
+        target_realm.hooks.dispatch(&event).await.unwrap_or_else(|e| {
+            error!(
+                "Error notifying capability removed for {}: {:?}",
+                target_realm.abs_moniker, e
+            )
+        });
+    }
+}
+
+/// Opens `name` inside `dir`, waiting for the `OnOpen` description so the returned node is
+/// confirmed ready before it's handed off in a `CapabilityReady` event.
+async fn open_watched_entry(dir: &DirectoryProxy, name: &str) -> Result<NodeProxy, ModelError> {
+    let (node, server_end) = fidl::endpoints::create_proxy::<NodeMarker>().unwrap();
+    dir.open(
+        fio::OPEN_RIGHT_READABLE | fio::OPEN_FLAG_DESCRIBE,
+        fio::MODE_TYPE_DIRECTORY,
+        name,
+        ServerEnd::new(server_end.into_channel()),
+    )
+    .map_err(|_| ModelError::open_directory_error(AbsoluteMoniker::root(), name.to_string()))?;
+
+    let mut events = node.take_event_stream();
+    match events.next().await {
+        Some(Ok(NodeEvent::OnOpen_ { s: status, info: _ })) => zx::Status::ok(status)
+            .map_err(|_| ModelError::open_directory_error(AbsoluteMoniker::root(), name.to_string()))
+            .map(|()| node),
+        _ => Err(ModelError::open_directory_error(AbsoluteMoniker::root(), name.to_string())),
+    }
 }
 
 async fn clone_outgoing_root(
@@ -238,24 +633,58 @@ async fn clone_outgoing_root(
 #[async_trait]
 impl EventSynthesisProvider for CapabilityReadyNotifier {
     async fn provide(&self, realm: Arc<Realm>, filter: EventFilter) -> Vec<Event> {
-        let maybe_outgoing_node_result =
-            async {
-                let execution = realm.lock_execution().await;
-                if execution.runtime.is_none() {
-                    return None;
-                }
+        // Grab a clone of the outgoing directory handle while `execution` is locked, then drop
+        // the guard before the open/OnOpen-wait retry loop below: that loop can wait up to
+        // `on_open_timeout` per attempt plus a `synthesis_retry_delay` sleep between attempts,
+        // and holding `lock_execution` across that many seconds of I/O waits and timers risks
+        // starving other execution-locked operations on this realm.
+        let maybe_out_dir: Option<Result<DirectoryProxy, ModelError>> = {
+            let execution = realm.lock_execution().await;
+            if execution.runtime.is_none() {
+                None
+            } else {
                 let runtime = execution.runtime.as_ref().unwrap();
-                let out_dir = match runtime.outgoing_dir.as_ref().ok_or(
-                    ModelError::open_directory_error(realm.abs_moniker.clone(), "/".to_string()),
-                ) {
-                    Ok(out_dir) => out_dir,
-                    Err(e) => return Some(Err(e)),
-                };
-                Some(clone_outgoing_root(&out_dir, &realm.abs_moniker).await)
+                Some(runtime.outgoing_dir.clone().ok_or_else(|| {
+                    ModelError::open_directory_error(realm.abs_moniker.clone(), "/".to_string())
+                }))
             }
-            .await;
+        };
+
+        // Synthesis can race a component that has only just started and hasn't yet begun serving
+        // its outgoing directory. Retry the open/OnOpen-wait a bounded number of times (like
+        // diagnostics readers bound their connect-and-read attempts) instead of giving up on the
+        // first transient failure.
+        let maybe_outgoing_dir_result: Option<Result<DirectoryProxy, ModelError>> =
+            match maybe_out_dir {
+                None => None,
+                Some(Err(e)) => Some(Err(e)),
+                Some(Ok(out_dir)) => {
+                    let mut attempt = 0;
+                    Some(loop {
+                        attempt += 1;
+                        let result: Result<DirectoryProxy, ModelError> = async {
+                            let node = clone_outgoing_root(&out_dir, &realm.abs_moniker).await?;
+                            self.wait_for_on_open(&node, &realm.abs_moniker, "/".to_string())
+                                .await?;
+                            io_util::node_to_directory(node).map_err(|_| {
+                                ModelError::open_directory_error(realm.abs_moniker.clone(), "/")
+                            })
+                        }
+                        .await;
+
+                        match result {
+                            Ok(dir) => break Ok(dir),
+                            Err(e) if attempt >= self.synthesis_retry_attempts => break Err(e),
+                            Err(_) => {
+                                fasync::Timer::new(fasync::Time::after(self.synthesis_retry_delay))
+                                    .await;
+                            }
+                        }
+                    })
+                }
+            };
 
-        let outgoing_node_result = match maybe_outgoing_node_result {
+        let outgoing_dir_result = match maybe_outgoing_dir_result {
             None => return vec![],
             Some(result) => result,
         };
@@ -283,7 +712,7 @@ impl EventSynthesisProvider for CapabilityReadyNotifier {
             })
             .collect();
 
-        self.create_events(outgoing_node_result, expose_decls, &realm).await
+        self.create_events_from_directory(outgoing_dir_result, expose_decls, &realm).await
     }
 }
 